@@ -0,0 +1,140 @@
+use crate::options::Options;
+use miette::{IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+#[cfg(feature = "persistent")]
+use redis::AsyncCommands;
+#[cfg(not(feature = "persistent"))]
+use std::collections::HashMap;
+
+/// Prefix of the Redis keys of cache entries
+#[cfg(feature = "persistent")]
+const KEY_PREFIX: &str = "daily-compprog:codeforces-cache:";
+
+/// Time after which stale entries are dropped, even though they could serve as fallback
+#[cfg(feature = "persistent")]
+const RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct CacheEntry {
+    /// Unix time in seconds at which the value was fetched
+    fetched_at: u64,
+    value: serde_json::Value,
+}
+
+/// Cache of Codeforces API results, keyed by URL and query parameters
+pub(super) struct Cache {
+    #[cfg(feature = "persistent")]
+    conn: Mutex<redis::aio::Connection>,
+    #[cfg(not(feature = "persistent"))]
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+fn unix_time_s() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Time for which a result of the given endpoint is served without asking Codeforces again
+pub(super) fn time_to_live(url: &str) -> Duration {
+    let method = url.trim_end_matches('/').rsplit('/').next().unwrap_or(url);
+    match method {
+        "problemset.problems" | "contest.list" => Duration::from_secs(60 * 60),
+        "user.info" => Duration::from_secs(10 * 60),
+        "contest.standings" => Duration::from_secs(5 * 60),
+        _ => Duration::from_secs(30),
+    }
+}
+
+pub(super) fn key(url: &str, query_params: &[(&str, &str)]) -> String {
+    let mut query_params = query_params.to_vec();
+    query_params.sort_unstable();
+
+    let mut key = String::from(url);
+    for (index, (name, value)) in query_params.into_iter().enumerate() {
+        key.push(if index == 0 { '?' } else { '&' });
+        key.push_str(name);
+        key.push('=');
+        key.push_str(value);
+    }
+    key
+}
+
+impl CacheEntry {
+    pub fn new<T: Serialize>(value: &T) -> Result<Self> {
+        Ok(Self {
+            fetched_at: unix_time_s(),
+            value: serde_json::to_value(value).into_diagnostic()?,
+        })
+    }
+
+    pub fn is_fresh(&self, time_to_live: Duration) -> bool {
+        unix_time_s() < self.fetched_at.saturating_add(time_to_live.as_secs())
+    }
+
+    pub fn value(&self) -> &serde_json::Value {
+        &self.value
+    }
+}
+
+#[cfg(not(feature = "persistent"))]
+impl Cache {
+    pub async fn open(_options: &Options) -> Result<Self> {
+        Ok(Self {
+            entries: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
+        Ok(self.entries.lock().await.get(key).cloned())
+    }
+
+    pub async fn insert(&self, key: String, entry: CacheEntry) -> Result<()> {
+        self.entries.lock().await.insert(key, entry);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl Cache {
+    pub async fn open(options: &Options) -> Result<Self> {
+        let conn = redis::Client::open(options.redis_host.as_str())
+            .into_diagnostic()?
+            .get_async_connection()
+            .await
+            .into_diagnostic()?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let serialized: Option<String> = self
+            .conn
+            .lock()
+            .await
+            .get(format!("{KEY_PREFIX}{key}"))
+            .await
+            .into_diagnostic()?;
+        serialized
+            .map(|serialized| serde_json::from_str(&serialized).into_diagnostic())
+            .transpose()
+    }
+
+    pub async fn insert(&self, key: String, entry: CacheEntry) -> Result<()> {
+        let serialized = serde_json::to_string(&entry).into_diagnostic()?;
+        self.conn
+            .lock()
+            .await
+            .set_ex(
+                format!("{KEY_PREFIX}{key}"),
+                serialized,
+                RETENTION.as_secs() as usize,
+            )
+            .await
+            .into_diagnostic()
+    }
+}
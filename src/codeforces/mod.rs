@@ -0,0 +1,673 @@
+mod cache;
+mod problem_index;
+mod submission_summary;
+
+use crate::options::Options;
+use cache::{Cache, CacheEntry};
+use governor::clock::DefaultClock;
+use governor::middleware::NoOpMiddleware;
+use governor::state::{InMemoryState, NotKeyed};
+use governor::{Jitter, Quota, RateLimiter};
+use miette::{miette, IntoDiagnostic, Result};
+pub use problem_index::{IndexedProblem, ProblemFilter, ProblemIndex};
+pub use submission_summary::SubmissionSummary;
+
+use chrono::{Datelike, TimeZone, Utc};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::*;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+pub const BASE: &str = "https://codeforces.com";
+pub const API_BASE: &str = "https://codeforces.com/api";
+/// Number of submissions requested per page when fetching submissions incrementally
+const SUBMISSION_PAGE_SIZE: u64 = 50;
+/// Prefix of the cache keys under which the submission summaries of handles are stored
+const SUBMISSION_SUMMARY_KEY_PREFIX: &str = "submission-summary:";
+pub const TAGS: &[&str] = &[
+    "2-sat",
+    "binary search",
+    "bitmasks",
+    "brute force",
+    "chinese remainder theorem",
+    "combinatorics",
+    "constructive algorithms",
+    "data structures",
+    "dfs and similar",
+    "divide and conquer",
+    "dp",
+    "dsu",
+    "expression parsing",
+    "fft",
+    "flows",
+    "games",
+    "geometry",
+    "graph matchings",
+    "graphs",
+    "greedy",
+    "hashing",
+    "implementation",
+    "math",
+    "matrices",
+    "meet-in-the-middle",
+    "number theory",
+    "probabilities",
+    "schedules",
+    "shortest paths",
+    "sortings",
+    "string suffix structures",
+    "strings",
+    "ternary search",
+    "trees",
+    "two pointers",
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct Handle(String);
+
+#[derive(Debug, Clone, Deserialize, Serialize, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct ProblemIdentifier(String);
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+pub struct Problem {
+    pub index: String,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub rating: Option<u64>,
+    #[serde(rename = "contestId")]
+    pub contest_id: Option<u64>,
+    #[serde(rename = "problemsetName")]
+    pub problemset_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+pub struct Contest {
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "startTimeSeconds")]
+    pub start_time_seconds: Option<i64>,
+}
+
+/// Kind of a contest as far as it can be told from its name
+#[derive(Debug, Clone, Copy, Hash, Deserialize, Serialize, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContestKind {
+    Div1,
+    Div2,
+    Div3,
+    Div4,
+    Educational,
+    Global,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+pub struct User {
+    pub handle: Handle,
+    pub rating: Option<u64>,
+    #[serde(rename = "maxRating")]
+    pub max_rating: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+pub struct PartyMember {
+    pub handle: Handle,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ParticipantType {
+    Contestant,
+    Practice,
+    Virtual,
+    Manager,
+    OutOfCompetition,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+pub struct Party {
+    #[serde(rename = "contestId")]
+    pub contest_id: Option<u64>,
+    pub members: Vec<PartyMember>,
+    #[serde(rename = "participantType")]
+    pub participant_type: Option<ParticipantType>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Deserialize, Serialize, Ord, PartialOrd, Eq, PartialEq)]
+pub enum VerdictCategory {
+    JudgingNotCompleted,
+    Incorrect,
+    Correct,
+}
+
+#[derive(Debug, Clone, Copy, Hash, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Verdict {
+    Failed,
+    Ok,
+    Partial,
+    CompilationError,
+    RuntimeError,
+    WrongAnswer,
+    PresentationError,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    IdlenessLimitExceeded,
+    SecurityViolated,
+    Crashed,
+    InputPreparationCrashed,
+    Challenged,
+    Skipped,
+    Testing,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+pub struct Submission {
+    pub id: u64,
+    #[serde(rename = "contestId")]
+    pub contest_id: u64,
+    pub problem: Problem,
+    pub author: Party,
+    pub verdict: Option<Verdict>,
+    #[serde(rename = "creationTimeSeconds")]
+    pub creation_time_seconds: i64,
+}
+
+pub struct Client {
+    rate_limiter: RateLimiter<NotKeyed, InMemoryState, DefaultClock, NoOpMiddleware>,
+    reqwest_client: reqwest::Client,
+    cache: Cache,
+    problem_index: RwLock<Option<Arc<ProblemIndex>>>,
+}
+
+impl From<String> for Handle {
+    fn from(str: String) -> Self {
+        Self(str)
+    }
+}
+impl Handle {
+    pub async fn from_checked(str: String, client: &Client) -> Option<Self> {
+        let url = format!("{API_BASE}/user.info/");
+        // check that requesting data about this handle gives an ok result
+        client
+            .call::<Vec<User>>(&url, &[("handles", &str)])
+            .await
+            .ok()
+            .map(|_| Handle(str))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub async fn get_submissions(&self, client: &Client) -> Result<Vec<Submission>> {
+        let url = format!("{API_BASE}/user.status");
+        client
+            .call_uncached(&url, &[("handle", self.as_str())])
+            .await
+    }
+
+    /// Gets all submissions with an id greater than `after_id`, newest first.
+    ///
+    /// Pages of recent submissions are requested until one contains an already known submission.
+    pub async fn get_submissions_after(
+        &self,
+        client: &Client,
+        after_id: Option<u64>,
+    ) -> Result<Vec<Submission>> {
+        let Some(after_id) = after_id else {
+            return self.get_submissions(client).await;
+        };
+
+        let url = format!("{API_BASE}/user.status");
+        let mut submissions = Vec::new();
+        let mut from = 1;
+        loop {
+            let page: Vec<Submission> = client
+                .call_uncached(
+                    &url,
+                    &[
+                        ("handle", self.as_str()),
+                        ("from", &from.to_string()),
+                        ("count", &SUBMISSION_PAGE_SIZE.to_string()),
+                    ],
+                )
+                .await?;
+            let page_len = page.len() as u64;
+            let known_reached = page.iter().any(|submission| submission.id <= after_id);
+
+            submissions.extend(
+                page.into_iter()
+                    .filter(|submission| submission.id > after_id),
+            );
+            if known_reached || page_len < SUBMISSION_PAGE_SIZE {
+                return Ok(submissions);
+            }
+            from += SUBMISSION_PAGE_SIZE;
+        }
+    }
+
+    /// Gets the most recent submissions, always asking Codeforces instead of the cache
+    pub async fn get_recent_submissions(
+        &self,
+        client: &Client,
+        count: u64,
+    ) -> Result<Vec<Submission>> {
+        let url = format!("{API_BASE}/user.status");
+        client
+            .call_uncached(
+                &url,
+                &[
+                    ("handle", self.as_str()),
+                    ("from", "1"),
+                    ("count", &count.to_string()),
+                ],
+            )
+            .await
+    }
+}
+
+impl Contest {
+    /// Kinds of the contest, where combined rounds like "Div. 1 + Div. 2" have several
+    pub fn kinds(&self) -> BTreeSet<ContestKind> {
+        if self.name.contains("Educational") {
+            BTreeSet::from([ContestKind::Educational])
+        } else if self.name.contains("Global Round") {
+            BTreeSet::from([ContestKind::Global])
+        } else {
+            [
+                ("Div. 1", ContestKind::Div1),
+                ("Div. 2", ContestKind::Div2),
+                ("Div. 3", ContestKind::Div3),
+                ("Div. 4", ContestKind::Div4),
+            ]
+            .into_iter()
+            .filter(|(division, _)| self.name.contains(division))
+            .map(|(_, kind)| kind)
+            .collect()
+        }
+    }
+
+    pub fn start_year(&self) -> Option<i32> {
+        Utc.timestamp_opt(self.start_time_seconds?, 0)
+            .single()
+            .map(|start_time| start_time.year())
+    }
+}
+
+impl ContestKind {
+    pub const ALL: [ContestKind; 6] = [
+        ContestKind::Div1,
+        ContestKind::Div2,
+        ContestKind::Div3,
+        ContestKind::Div4,
+        ContestKind::Educational,
+        ContestKind::Global,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContestKind::Div1 => "div1",
+            ContestKind::Div2 => "div2",
+            ContestKind::Div3 => "div3",
+            ContestKind::Div4 => "div4",
+            ContestKind::Educational => "educational",
+            ContestKind::Global => "global",
+        }
+    }
+}
+
+impl ProblemIdentifier {
+    pub fn new(contest_id: u64, index: &str) -> Self {
+        Self(format!("{}/{}", contest_id, index.to_uppercase()))
+    }
+
+    /// Parses the URL of a problem, either in its contest or in the problemset
+    pub fn from_url(url: &str) -> Option<Self> {
+        let (_, path) = url.split_once("codeforces.com/")?;
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        let segments: Vec<_> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let (contest_id, index) = match segments.as_slice() {
+            ["contest", contest_id, "problem", index]
+            | ["problemset", "problem", contest_id, index] => (contest_id, index),
+            _ => return None,
+        };
+        if index.is_empty() || !index.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(Self::new(contest_id.parse().ok()?, index))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Problem {
+    pub fn url(&self) -> Result<String> {
+        Ok(format!(
+            "{BASE}/contest/{}/problem/{}",
+            self.contest_id.ok_or_else(|| miette!(
+                "Don't know how to synthesize URL of problem without contest_id"
+            ))?,
+            self.index
+        ))
+    }
+
+    pub fn identifier(&self) -> Result<ProblemIdentifier> {
+        Ok(ProblemIdentifier(format!(
+            "{}/{}",
+            self.contest_id
+                .ok_or_else(|| miette!("Don't know how to identify problem without contest_id"))?,
+            self.index
+        )))
+    }
+}
+
+impl Submission {
+    /// Whether the verdict of this submission may still change
+    pub fn is_pending(&self) -> bool {
+        matches!(self.verdict, None | Some(Verdict::Testing))
+    }
+}
+
+impl Verdict {
+    pub fn category(&self) -> VerdictCategory {
+        use Verdict::*;
+        use VerdictCategory::*;
+        match self {
+            Ok => Correct,
+            Partial
+            | WrongAnswer
+            | PresentationError
+            | TimeLimitExceeded
+            | MemoryLimitExceeded
+            | IdlenessLimitExceeded
+            | Challenged
+            | RuntimeError => Incorrect,
+            Failed
+            | SecurityViolated
+            | Crashed
+            | InputPreparationCrashed
+            | Rejected
+            | Skipped
+            | Testing
+            | CompilationError => JudgingNotCompleted,
+        }
+    }
+}
+
+impl Handle {}
+
+impl Client {
+    pub async fn open(options: &Options) -> Result<Self> {
+        Ok(Self {
+            rate_limiter: RateLimiter::direct(Quota::with_period(Duration::from_secs(3)).unwrap()),
+            reqwest_client: reqwest::Client::new(),
+            cache: Cache::open(options).await?,
+            problem_index: RwLock::new(None),
+        })
+    }
+
+    async fn call<T>(&self, url: &str, query_params: &[(&str, &str)]) -> Result<T>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        self.call_with_cache(url, query_params, true).await
+    }
+
+    /// Calls the API without serving a fresh cached result, for results that have to be current
+    async fn call_uncached<T>(&self, url: &str, query_params: &[(&str, &str)]) -> Result<T>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        self.call_with_cache(url, query_params, false).await
+    }
+
+    async fn call_with_cache<T>(
+        &self,
+        url: &str,
+        query_params: &[(&str, &str)],
+        serve_fresh: bool,
+    ) -> Result<T>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let cache_key = cache::key(url, query_params);
+        let cached = self.cache.get(&cache_key).await.unwrap_or_else(|report| {
+            log::warn!("Could not read cache for {}\n{}", cache_key, report);
+            None
+        });
+
+        // serve fresh enough results without asking codeforces
+        if let Some(entry) = cached.as_ref().filter(|_| serve_fresh) {
+            if entry.is_fresh(cache::time_to_live(url)) {
+                log::trace!("Fresh in cache: {}", cache_key);
+                return serde_json::from_value(entry.value().clone()).into_diagnostic();
+            }
+        }
+
+        self.rate_limiter
+            .until_ready_with_jitter(Jitter::new(
+                Duration::from_millis(50),
+                Duration::from_millis(500),
+            ))
+            .await;
+
+        #[derive(Debug, Clone, Deserialize, Serialize)]
+        struct CallResponse<U> {
+            status: String,
+            comment: Option<String>,
+            result: Option<U>,
+        }
+
+        let response = self
+            .reqwest_client
+            .get(url)
+            .query(query_params)
+            .send()
+            .await
+            .into_diagnostic()?;
+
+        // handle too many requests
+        if response.status() == StatusCode::from_u16(503).into_diagnostic()? {
+            log::warn!("Too many requests to codeforces -- using cache");
+            if let Some(entry) = cached {
+                log::debug!("\tCached: {}", cache_key);
+                return serde_json::from_value(entry.value().clone()).into_diagnostic();
+            } else {
+                log::warn!("\tNot cached: {}", cache_key);
+            }
+        }
+
+        // handle normal response
+        let call_response = response.json::<CallResponse<T>>().await.into_diagnostic()?;
+        miette::ensure!(
+            call_response.status == "OK",
+            "Codeforces did not complete the request. Comment: {:?}",
+            call_response.comment,
+        );
+        let result = call_response
+            .result
+            .ok_or_else(|| miette!("Codeforces did not provide a result"))?;
+
+        // cache result
+        if let Err(report) = self
+            .cache
+            .insert(cache_key.clone(), CacheEntry::new(&result)?)
+            .await
+        {
+            log::warn!("Could not cache {}\n{}", cache_key, report);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn get_users(&self, handles: impl Iterator<Item = &Handle>) -> Result<Vec<User>> {
+        let url = format!("{API_BASE}/user.info");
+        let handles_string: String = handles.map(Handle::as_str).collect::<Vec<_>>().join(";");
+        self.call(&url, &[("handles", &handles_string)]).await
+    }
+
+    /// Gets the summary of all submissions of a handle, fetching only the submissions
+    /// that are not summarized yet.
+    ///
+    /// Summaries are kept in the cache, so with the `persistent` feature they survive restarts.
+    /// Summaries of handles no longer registered anywhere expire with the cache retention.
+    pub async fn submission_summary(&self, handle: &Handle) -> Result<SubmissionSummary> {
+        let key = format!("{SUBMISSION_SUMMARY_KEY_PREFIX}{}", handle.as_str());
+        let mut summary: SubmissionSummary = match self.cache.get(&key).await {
+            Ok(Some(entry)) => {
+                serde_json::from_value(entry.value().clone()).unwrap_or_else(|err| {
+                    log::warn!(
+                        "Could not read submission summary of {}\n{}",
+                        handle.as_str(),
+                        err
+                    );
+                    SubmissionSummary::default()
+                })
+            }
+            Ok(None) => SubmissionSummary::default(),
+            Err(report) => {
+                log::warn!("Could not read cache for {}\n{}", key, report);
+                SubmissionSummary::default()
+            }
+        };
+
+        let submissions = handle
+            .get_submissions_after(self, summary.complete_up_to)
+            .await?;
+        summary.merge(submissions)?;
+
+        if let Err(report) = self
+            .cache
+            .insert(key.clone(), CacheEntry::new(&summary)?)
+            .await
+        {
+            log::warn!("Could not cache {}\n{}", key, report);
+        }
+        Ok(summary)
+    }
+
+    /// Gets the problems of a contest, in the order of the contest
+    pub async fn get_contest_problems(&self, contest_id: u64) -> Result<Vec<Problem>> {
+        let url = format!("{API_BASE}/contest.standings");
+
+        #[derive(Debug, Clone, Deserialize, Serialize)]
+        struct CallResponse {
+            problems: Vec<Problem>,
+        }
+
+        let contest_id = contest_id.to_string();
+        let call_response = self
+            .call::<CallResponse>(
+                &url,
+                &[("contestId", &contest_id), ("from", "1"), ("count", "1")],
+            )
+            .await?;
+        Ok(call_response.problems)
+    }
+
+    /// Gets the index of all problems, downloading it again if it is outdated
+    pub async fn problem_index(&self) -> Result<Arc<ProblemIndex>> {
+        if let Some(problem_index) = self.problem_index.read().await.as_ref() {
+            if !problem_index.is_outdated() {
+                return Ok(problem_index.clone());
+            }
+        }
+
+        let mut problem_index_opt = self.problem_index.write().await;
+        // the index may have been refreshed while waiting for the lock
+        if let Some(problem_index) = problem_index_opt.as_ref() {
+            if !problem_index.is_outdated() {
+                return Ok(problem_index.clone());
+            }
+        }
+
+        match ProblemIndex::fetch(self).await {
+            Ok(problem_index) => {
+                let problem_index = Arc::new(problem_index);
+                *problem_index_opt = Some(problem_index.clone());
+                Ok(problem_index)
+            }
+            Err(report) => match problem_index_opt.as_ref() {
+                Some(problem_index) => {
+                    log::warn!("Could not refresh problem index -- using outdated one\n{report}");
+                    Ok(problem_index.clone())
+                }
+                None => Err(report),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problem_identifier_from_url() {
+        let expected = Some(ProblemIdentifier::new(1700, "C"));
+        assert_eq!(
+            ProblemIdentifier::from_url("https://codeforces.com/contest/1700/problem/C"),
+            expected
+        );
+        assert_eq!(
+            ProblemIdentifier::from_url("http://www.codeforces.com/problemset/problem/1700/c/"),
+            expected
+        );
+        assert_eq!(
+            ProblemIdentifier::from_url("codeforces.com/contest/1700/problem/C?locale=en#note"),
+            expected
+        );
+    }
+
+    #[test]
+    fn problem_identifier_from_invalid_url() {
+        assert_eq!(
+            ProblemIdentifier::from_url("https://example.com/contest/1/problem/A"),
+            None
+        );
+        assert_eq!(
+            ProblemIdentifier::from_url("https://codeforces.com/gym/100001/problem/A"),
+            None
+        );
+        assert_eq!(
+            ProblemIdentifier::from_url("https://codeforces.com/contest/x/problem/A"),
+            None
+        );
+        assert_eq!(
+            ProblemIdentifier::from_url("https://codeforces.com/contest/1/problem/A-1"),
+            None
+        );
+        assert_eq!(
+            ProblemIdentifier::from_url("https://codeforces.com/contest/1"),
+            None
+        );
+    }
+
+    #[test]
+    fn problem_identifier_matches_problem() {
+        let problem = Problem {
+            index: String::from("B1"),
+            name: String::from("Easy Version"),
+            tags: Vec::new(),
+            rating: None,
+            contest_id: Some(1700),
+            problemset_name: None,
+        };
+        assert_eq!(
+            problem.identifier().unwrap(),
+            ProblemIdentifier::new(1700, "b1")
+        );
+    }
+}
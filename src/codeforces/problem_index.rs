@@ -0,0 +1,188 @@
+use crate::codeforces::{Client, Contest, ContestKind, Problem, ProblemIdentifier, API_BASE};
+use miette::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
+/// Age after which the index is downloaded again
+const MAX_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+/// Age after which an index without contest metadata is downloaded again
+const INCOMPLETE_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
+pub struct ProblemStatistics {
+    #[serde(rename = "contestId")]
+    pub contest_id: Option<u64>,
+    pub index: String,
+    #[serde(rename = "solvedCount")]
+    pub solved_count: u64,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IndexedProblem {
+    pub problem: Problem,
+    pub solved_count: u64,
+    /// Empty if the kind of the contest is unknown
+    pub contest_kinds: BTreeSet<ContestKind>,
+    pub contest_year: Option<i32>,
+}
+
+/// Restrictions on the problems returned by [`ProblemIndex::query`]; unset fields do not restrict
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ProblemFilter {
+    pub rating: Option<RangeInclusive<u64>>,
+    /// Problems need to have all of these tags
+    pub tags: Vec<String>,
+    /// Problems must not have any of these tags
+    pub excluded_tags: Vec<String>,
+    pub contest_id: Option<u64>,
+    pub solved_count: Option<RangeInclusive<u64>>,
+    /// If not empty, problems need to be from a contest of one of these kinds
+    pub contest_kinds: BTreeSet<ContestKind>,
+    pub min_contest_year: Option<i32>,
+}
+
+/// In-memory copy of the whole Codeforces problemset
+#[derive(Debug, Clone)]
+pub struct ProblemIndex {
+    problems: Vec<IndexedProblem>,
+    /// Whether the contests of the problems are known
+    has_contests: bool,
+    /// Position of every problem with a contest in `problems`
+    positions: HashMap<ProblemIdentifier, usize>,
+    fetched_at: Instant,
+}
+
+impl ProblemFilter {
+    pub fn matches(&self, indexed_problem: &IndexedProblem) -> bool {
+        let problem = &indexed_problem.problem;
+        self.rating.as_ref().is_none_or(|rating_range| {
+            problem
+                .rating
+                .is_some_and(|rating| rating_range.contains(&rating))
+        }) && self.tags.iter().all(|tag| problem.tags.contains(tag))
+            && !self
+                .excluded_tags
+                .iter()
+                .any(|tag| problem.tags.contains(tag))
+            && self
+                .contest_id
+                .is_none_or(|contest_id| problem.contest_id == Some(contest_id))
+            && self
+                .solved_count
+                .as_ref()
+                .is_none_or(|solved_count| solved_count.contains(&indexed_problem.solved_count))
+            && (self.contest_kinds.is_empty()
+                || !self
+                    .contest_kinds
+                    .is_disjoint(&indexed_problem.contest_kinds))
+            && self.min_contest_year.is_none_or(|min_contest_year| {
+                indexed_problem
+                    .contest_year
+                    .is_some_and(|contest_year| contest_year >= min_contest_year)
+            })
+    }
+}
+
+impl ProblemIndex {
+    pub(super) async fn fetch(client: &Client) -> Result<Self> {
+        let url = format!("{API_BASE}/problemset.problems");
+
+        #[derive(Debug, Clone, Deserialize, Serialize)]
+        struct CallResponse {
+            problems: Vec<Problem>,
+            #[serde(rename = "problemStatistics")]
+            problem_statistics: Vec<ProblemStatistics>,
+        }
+
+        let call_response = client.call::<CallResponse>(&url, &[]).await?;
+        // without contests, problems can still be drawn as long as no contest filter is set
+        let contests: HashMap<u64, Contest> = match client
+            .call::<Vec<Contest>>(&format!("{API_BASE}/contest.list"), &[("gym", "false")])
+            .await
+        {
+            Ok(contests) => contests
+                .into_iter()
+                .map(|contest| (contest.id, contest))
+                .collect(),
+            Err(report) => {
+                log::warn!("Could not get contests -- indexing problems without them\n{report}");
+                HashMap::new()
+            }
+        };
+        let has_contests = !contests.is_empty();
+        let solved_counts: HashMap<_, _> = call_response
+            .problem_statistics
+            .into_iter()
+            .map(|statistics| {
+                (
+                    (statistics.contest_id, statistics.index),
+                    statistics.solved_count,
+                )
+            })
+            .collect();
+        let problems = call_response
+            .problems
+            .into_iter()
+            .map(|problem| {
+                let solved_count = solved_counts
+                    .get(&(problem.contest_id, problem.index.clone()))
+                    .copied()
+                    .unwrap_or_default();
+                let contest = problem
+                    .contest_id
+                    .and_then(|contest_id| contests.get(&contest_id));
+                IndexedProblem {
+                    problem,
+                    solved_count,
+                    contest_kinds: contest.map(Contest::kinds).unwrap_or_default(),
+                    contest_year: contest.and_then(Contest::start_year),
+                }
+            })
+            .collect::<Vec<_>>();
+        let positions = problems
+            .iter()
+            .enumerate()
+            .filter_map(|(position, indexed_problem)| {
+                Some((indexed_problem.problem.identifier().ok()?, position))
+            })
+            .collect();
+        log::info!("Fetched problem index with {} problems", problems.len());
+
+        Ok(Self {
+            problems,
+            has_contests,
+            positions,
+            fetched_at: Instant::now(),
+        })
+    }
+
+    pub(super) fn is_outdated(&self) -> bool {
+        let max_age = if self.has_contests {
+            MAX_AGE
+        } else {
+            INCOMPLETE_MAX_AGE
+        };
+        self.fetched_at.elapsed() >= max_age
+    }
+
+    pub fn problems(&self) -> &[IndexedProblem] {
+        &self.problems
+    }
+
+    pub fn get(&self, problem_id: &ProblemIdentifier) -> Option<&IndexedProblem> {
+        self.positions
+            .get(problem_id)
+            .map(|position| &self.problems[*position])
+    }
+
+    pub fn query<'a>(
+        &'a self,
+        filter: &'a ProblemFilter,
+    ) -> impl Iterator<Item = &'a IndexedProblem> + 'a {
+        self.problems
+            .iter()
+            .filter(move |indexed_problem| filter.matches(indexed_problem))
+    }
+}
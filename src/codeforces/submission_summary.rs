@@ -0,0 +1,167 @@
+use crate::codeforces::{ParticipantType, ProblemIdentifier, Submission, VerdictCategory};
+use miette::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Verdicts of a handle, built incrementally from its submissions
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SubmissionSummary {
+    /// All submissions with an id up to this one have a final verdict in the summary
+    pub(super) complete_up_to: Option<u64>,
+    verdict_per_problem: HashMap<ProblemIdentifier, VerdictCategory>,
+    /// Unix time in seconds of the last submission per contest the handle took part in
+    participations: HashMap<u64, i64>,
+}
+
+impl SubmissionSummary {
+    /// Merges newly fetched submissions, given newest first
+    pub(super) fn merge(&mut self, submissions: Vec<Submission>) -> Result<()> {
+        // pending submissions have to be fetched again, until their verdict is final
+        let oldest_pending_id = submissions
+            .iter()
+            .filter(|submission| submission.is_pending())
+            .map(|submission| submission.id)
+            .min();
+        let newest_id = submissions.iter().map(|submission| submission.id).max();
+        self.complete_up_to = match oldest_pending_id {
+            Some(oldest_pending_id) => Some(oldest_pending_id - 1),
+            None => Ord::max(self.complete_up_to, newest_id),
+        };
+
+        for submission in submissions {
+            let took_part = matches!(
+                submission.author.participant_type,
+                Some(ParticipantType::Contestant | ParticipantType::OutOfCompetition)
+            );
+            if let Some(contest_id) = submission.author.contest_id.filter(|_| took_part) {
+                let last_participation = self.participations.entry(contest_id).or_default();
+                *last_participation =
+                    Ord::max(*last_participation, submission.creation_time_seconds);
+            }
+            if let Some(verdict) = submission.verdict {
+                self.verdict_per_problem
+                    .entry(submission.problem.identifier()?)
+                    .and_modify(|previous_category| {
+                        *previous_category = Ord::max(*previous_category, verdict.category());
+                    })
+                    .or_insert_with(|| verdict.category());
+            }
+        }
+        Ok(())
+    }
+
+    /// Best verdict category per problem the handle submitted to
+    pub fn verdict_per_problem(&self) -> &HashMap<ProblemIdentifier, VerdictCategory> {
+        &self.verdict_per_problem
+    }
+
+    /// Time of the last submission per contest the handle took part in, live or out of competition
+    pub fn participations(&self) -> &HashMap<u64, i64> {
+        &self.participations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codeforces::{Party, Problem, Verdict};
+
+    fn submission(id: u64, index: &str, verdict: Option<Verdict>) -> Submission {
+        participant_submission(id, index, verdict, ParticipantType::Practice)
+    }
+
+    fn participant_submission(
+        id: u64,
+        index: &str,
+        verdict: Option<Verdict>,
+        participant_type: ParticipantType,
+    ) -> Submission {
+        Submission {
+            id,
+            contest_id: 1000,
+            problem: Problem {
+                index: String::from(index),
+                name: String::from(index),
+                tags: Vec::new(),
+                rating: None,
+                contest_id: Some(1000),
+                problemset_name: None,
+            },
+            author: Party {
+                contest_id: Some(1000),
+                members: Vec::new(),
+                participant_type: Some(participant_type),
+            },
+            verdict,
+            creation_time_seconds: id as i64,
+        }
+    }
+
+    #[test]
+    fn merge_keeps_best_verdict() {
+        let mut summary = SubmissionSummary::default();
+        summary
+            .merge(vec![
+                submission(3, "A", Some(Verdict::WrongAnswer)),
+                submission(2, "A", Some(Verdict::Ok)),
+                submission(1, "B", Some(Verdict::CompilationError)),
+            ])
+            .unwrap();
+        summary
+            .merge(vec![submission(4, "B", Some(Verdict::TimeLimitExceeded))])
+            .unwrap();
+
+        assert_eq!(summary.complete_up_to, Some(4));
+        assert_eq!(
+            summary.verdict_per_problem()[&ProblemIdentifier::new(1000, "A")],
+            VerdictCategory::Correct
+        );
+        assert_eq!(
+            summary.verdict_per_problem()[&ProblemIdentifier::new(1000, "B")],
+            VerdictCategory::Incorrect
+        );
+    }
+
+    #[test]
+    fn merge_stops_before_pending_submissions() {
+        let mut summary = SubmissionSummary::default();
+        summary
+            .merge(vec![
+                submission(7, "A", Some(Verdict::Ok)),
+                submission(6, "B", Some(Verdict::Testing)),
+                submission(5, "C", None),
+            ])
+            .unwrap();
+        assert_eq!(summary.complete_up_to, Some(4));
+        assert!(!summary
+            .verdict_per_problem()
+            .contains_key(&ProblemIdentifier::new(1000, "C")));
+
+        // once judged, the pending submissions are fetched again
+        summary
+            .merge(vec![
+                submission(7, "A", Some(Verdict::Ok)),
+                submission(6, "B", Some(Verdict::WrongAnswer)),
+                submission(5, "C", Some(Verdict::Ok)),
+            ])
+            .unwrap();
+        assert_eq!(summary.complete_up_to, Some(7));
+        assert_eq!(
+            summary.verdict_per_problem()[&ProblemIdentifier::new(1000, "C")],
+            VerdictCategory::Correct
+        );
+    }
+
+    #[test]
+    fn merge_records_participations() {
+        let mut summary = SubmissionSummary::default();
+        summary
+            .merge(vec![
+                participant_submission(9, "B", None, ParticipantType::Virtual),
+                participant_submission(8, "B", None, ParticipantType::OutOfCompetition),
+                participant_submission(7, "A", None, ParticipantType::Contestant),
+            ])
+            .unwrap();
+        assert_eq!(summary.participations(), &HashMap::from([(1000, 8)]));
+    }
+}
@@ -15,14 +15,14 @@ async fn main() -> Result<()> {
     // Query command line options and initialize logging
     let opts = options::parse()?;
 
+    let cf_client_arc = Arc::new(codeforces::Client::open(&opts).await?);
+    let cf_client_arc2 = cf_client_arc.clone();
+    let cf_client_arc1 = cf_client_arc;
+
     let opts_arc = Arc::new(opts);
     let opts_arc2 = opts_arc.clone();
     let opts_arc1 = opts_arc;
 
-    let cf_client_arc = Arc::new(codeforces::Client::new());
-    let cf_client_arc2 = cf_client_arc.clone();
-    let cf_client_arc1 = cf_client_arc;
-
     let (sched_send, sched_recv) = mpsc::unbounded_channel();
     let (telegram_send, telegram_recv) = mpsc::unbounded_channel();
     let telegram_send_clone = telegram_send.clone();
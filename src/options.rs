@@ -11,10 +11,14 @@ pub struct Options {
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
 
-    /// Cron options for message schedule
+    /// Cron options for message schedule (in local time) of chats without their own schedule
     #[arg(long, default_value_t = String::from("0 30 7 * * * *"))]
     pub messages_cron: String,
 
+    /// Minutes users have to submit the compilation error verifying their Codeforces handle
+    #[arg(long, default_value_t = 5)]
+    pub verification_minutes: u64,
+
     /// Address of Redis instance
     #[cfg(feature = "persistent")]
     #[arg(long, default_value_t = String::from("redis://localhost:6379"))]
@@ -0,0 +1,102 @@
+use crate::options::Options;
+use miette::Result;
+use teloxide::prelude::ChatId;
+use tokio::sync::Mutex;
+
+#[cfg(feature = "persistent")]
+use miette::IntoDiagnostic;
+#[cfg(feature = "persistent")]
+use redis::AsyncCommands;
+#[cfg(not(feature = "persistent"))]
+use std::collections::HashSet;
+
+/// Key of the Redis set containing all chats with running daily messages
+#[cfg(feature = "persistent")]
+const ACTIVE_CHATS_KEY: &str = "daily-compprog:active-chats";
+
+/// Durable record of the chats the scheduler has jobs for, so they can be restored on boot
+pub(super) struct ActiveChats {
+    #[cfg(feature = "persistent")]
+    conn: Mutex<redis::aio::Connection>,
+    #[cfg(not(feature = "persistent"))]
+    chats: Mutex<HashSet<ChatId>>,
+}
+
+#[cfg(not(feature = "persistent"))]
+impl ActiveChats {
+    pub async fn open(_options: &Options) -> Result<Self> {
+        Ok(Self {
+            chats: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub async fn insert(&self, chat_id: ChatId) -> Result<()> {
+        self.chats.lock().await.insert(chat_id);
+        Ok(())
+    }
+
+    pub async fn remove(&self, chat_id: ChatId) -> Result<()> {
+        self.chats.lock().await.remove(&chat_id);
+        Ok(())
+    }
+
+    pub async fn contains(&self, chat_id: ChatId) -> Result<bool> {
+        Ok(self.chats.lock().await.contains(&chat_id))
+    }
+
+    pub async fn all(&self) -> Result<Vec<ChatId>> {
+        Ok(self.chats.lock().await.iter().copied().collect())
+    }
+}
+
+#[cfg(feature = "persistent")]
+impl ActiveChats {
+    pub async fn open(options: &Options) -> Result<Self> {
+        let conn = redis::Client::open(options.redis_host.as_str())
+            .into_diagnostic()?
+            .get_async_connection()
+            .await
+            .into_diagnostic()?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub async fn insert(&self, ChatId(chat_id): ChatId) -> Result<()> {
+        self.conn
+            .lock()
+            .await
+            .sadd(ACTIVE_CHATS_KEY, chat_id)
+            .await
+            .into_diagnostic()
+    }
+
+    pub async fn remove(&self, ChatId(chat_id): ChatId) -> Result<()> {
+        self.conn
+            .lock()
+            .await
+            .srem(ACTIVE_CHATS_KEY, chat_id)
+            .await
+            .into_diagnostic()
+    }
+
+    pub async fn contains(&self, ChatId(chat_id): ChatId) -> Result<bool> {
+        self.conn
+            .lock()
+            .await
+            .sismember(ACTIVE_CHATS_KEY, chat_id)
+            .await
+            .into_diagnostic()
+    }
+
+    pub async fn all(&self) -> Result<Vec<ChatId>> {
+        let chat_ids: Vec<i64> = self
+            .conn
+            .lock()
+            .await
+            .smembers(ACTIVE_CHATS_KEY)
+            .await
+            .into_diagnostic()?;
+        Ok(chat_ids.into_iter().map(ChatId).collect())
+    }
+}
@@ -1,6 +1,6 @@
 use crate::codeforces;
 use crate::options::Options;
-use crate::scheduler::{daily_message, updater, MyScheduler, SchedulerStorage};
+use crate::scheduler::{daily_message, updater, ActiveChats, MyScheduler, SchedulerStorage};
 use crate::telegram_bot::TelegramControlCommand;
 use miette::*;
 use std::sync::Arc;
@@ -9,8 +9,14 @@ use tokio::sync::{mpsc, RwLock};
 use SchedulerControlCommand::*;
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum SchedulerControlCommand {
     StartDailyMessages { chat_id: ChatId },
+    RestoreDailyMessages { chat_id: ChatId },
+    RescheduleDailyMessages { chat_id: ChatId },
+    PauseDailyMessages { chat_id: ChatId },
+    ResumeDailyMessages { chat_id: ChatId },
+    StopDailyMessages { chat_id: ChatId },
 }
 
 pub(super) async fn handle(
@@ -18,28 +24,133 @@ pub(super) async fn handle(
     options: Arc<Options>,
     sched_storage_rw: Arc<RwLock<SchedulerStorage>>,
     scheduler_rw: Arc<RwLock<MyScheduler>>,
+    active_chats: Arc<ActiveChats>,
     telegram_send: Arc<mpsc::UnboundedSender<TelegramControlCommand>>,
     cf_client: Arc<codeforces::Client>,
 ) -> Result<()> {
     match command {
         StartDailyMessages { chat_id } => {
-            daily_message::start(
+            start_jobs(
                 options,
-                chat_id,
-                sched_storage_rw.clone(),
-                scheduler_rw.clone(),
-                telegram_send.clone(),
-                cf_client.clone(),
-            )
-            .await?;
-            updater::start(
                 chat_id,
                 sched_storage_rw,
                 scheduler_rw,
                 telegram_send,
                 cf_client,
             )
-            .await
+            .await?;
+            active_chats.insert(chat_id).await
+        }
+        ResumeDailyMessages { chat_id } => {
+            // pausing a chat that was never started must not start it
+            let text = if active_chats.contains(chat_id).await? {
+                start_jobs(
+                    options,
+                    chat_id,
+                    sched_storage_rw,
+                    scheduler_rw,
+                    telegram_send.clone(),
+                    cf_client,
+                )
+                .await?;
+                "Daily problems are resumed"
+            } else {
+                "Daily problems were never started in this chat. Use /start to start them"
+            };
+            telegram_send
+                .send(TelegramControlCommand::SendMessage {
+                    chat_id,
+                    text: String::from(text),
+                })
+                .into_diagnostic()
+        }
+        RestoreDailyMessages { chat_id } => {
+            let channel_state =
+                crate::util::get_channel_state(chat_id, telegram_send.as_ref()).await?;
+            if channel_state.is_paused() {
+                log::info!("Not restoring daily messages for {chat_id}, as they are paused");
+                Ok(())
+            } else {
+                log::info!("Restoring daily messages for {chat_id}");
+                start_jobs(
+                    options,
+                    chat_id,
+                    sched_storage_rw,
+                    scheduler_rw,
+                    telegram_send,
+                    cf_client,
+                )
+                .await
+            }
+        }
+        RescheduleDailyMessages { chat_id } => {
+            let is_started = sched_storage_rw
+                .read()
+                .await
+                .daily_message_job_ids
+                .contains_key(&chat_id);
+            if is_started {
+                daily_message::start(
+                    options,
+                    chat_id,
+                    sched_storage_rw,
+                    scheduler_rw,
+                    telegram_send,
+                    cf_client,
+                )
+                .await
+            } else {
+                log::debug!(
+                    "Not rescheduling daily messages for {chat_id}, as they are not started"
+                );
+                Ok(())
+            }
+        }
+        PauseDailyMessages { chat_id } => {
+            stop_jobs(chat_id, sched_storage_rw, scheduler_rw).await;
+            Ok(())
+        }
+        StopDailyMessages { chat_id } => {
+            stop_jobs(chat_id, sched_storage_rw, scheduler_rw).await;
+            active_chats.remove(chat_id).await
+        }
+    }
+}
+
+async fn start_jobs(
+    options: Arc<Options>,
+    chat_id: ChatId,
+    sched_storage_rw: Arc<RwLock<SchedulerStorage>>,
+    scheduler_rw: Arc<RwLock<MyScheduler>>,
+    telegram_send: Arc<mpsc::UnboundedSender<TelegramControlCommand>>,
+    cf_client: Arc<codeforces::Client>,
+) -> Result<()> {
+    daily_message::start(
+        options,
+        chat_id,
+        sched_storage_rw.clone(),
+        scheduler_rw,
+        telegram_send,
+        cf_client,
+    )
+    .await?;
+    updater::add_chat(chat_id, sched_storage_rw).await;
+    Ok(())
+}
+
+async fn stop_jobs(
+    chat_id: ChatId,
+    sched_storage_rw: Arc<RwLock<SchedulerStorage>>,
+    scheduler_rw: Arc<RwLock<MyScheduler>>,
+) {
+    {
+        let mut scheduler = scheduler_rw.write().await;
+        let mut sched_storage = sched_storage_rw.write().await;
+
+        if let Some(job_id) = sched_storage.daily_message_job_ids.remove(&chat_id) {
+            scheduler.remove(job_id);
+            log::info!("Removed daily messages for {chat_id}");
         }
     }
+    updater::remove_chat(chat_id, sched_storage_rw).await;
 }
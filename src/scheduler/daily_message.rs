@@ -2,7 +2,11 @@ use crate::codeforces;
 use crate::options::Options;
 use crate::scheduler::{util, MyScheduler, SchedulerStorage};
 use crate::telegram_bot::TelegramControlCommand;
-use crate::telegram_bot::TelegramControlCommand::SetAndNotifyDailyProblem;
+use crate::telegram_bot::ReviewMode;
+use crate::telegram_bot::TelegramControlCommand::{
+    SendMessage, SendReview, SetAndNotifyDailyProblem,
+};
+use chrono::Local;
 use std::sync::Arc;
 use teloxide::prelude::*;
 use tokio::sync::{mpsc, RwLock};
@@ -16,12 +20,35 @@ async fn daily_message(
     let channel_state = crate::util::get_channel_state(chat_id, telegram_send).await?;
 
     log::info!("Starting to prepare daily message for {chat_id:?}");
-    let problem = channel_state.find_daily_problem(cf_client, chat_id).await?;
-
-    log::info!("Sending daily message to {:?}", chat_id);
-    telegram_send
-        .send(SetAndNotifyDailyProblem { chat_id, problem })
-        .into_diagnostic()
+    match channel_state
+        .find_daily_problems(cf_client, chat_id, channel_state.today())
+        .await?
+    {
+        Some(problems) => {
+            log::info!("Sending daily message to {:?}", chat_id);
+            telegram_send
+                .send(SetAndNotifyDailyProblem {
+                    chat_id,
+                    problems,
+                    with_review: true,
+                })
+                .into_diagnostic()?;
+        }
+        None => {
+            log::warn!("Found no daily problem for {:?}", chat_id);
+            telegram_send
+                .send(SendMessage {
+                    chat_id,
+                    text: channel_state.no_daily_problem_text(cf_client).await,
+                })
+                .into_diagnostic()?;
+            // the daily message sends the review otherwise
+            if channel_state.review_mode() != ReviewMode::Off {
+                telegram_send.send(SendReview { chat_id }).into_diagnostic()?;
+            }
+        }
+    }
+    Ok(())
 }
 
 pub(super) async fn start(
@@ -32,10 +59,10 @@ pub(super) async fn start(
     telegram_send: Arc<mpsc::UnboundedSender<TelegramControlCommand>>,
     cf_client: Arc<codeforces::Client>,
 ) -> Result<()> {
-    log::info!("Registered daily messages for {chat_id}");
+    let channel_state = crate::util::get_channel_state(chat_id, telegram_send.as_ref()).await?;
     let mut scheduler = scheduler_rw.as_ref().write().await;
 
-    let job_id = util::register_to_schedule(&options.messages_cron, &mut scheduler, move |_id| {
+    let command = move |_id| {
         let telegram_send_clone = telegram_send.clone();
         let cf_client_clone = cf_client.clone();
         tokio::spawn(async move {
@@ -47,8 +74,28 @@ pub(super) async fn start(
             .await
             .unwrap()
         });
-    })
-    .await?;
+    };
+    let job_id = match channel_state.message_schedule() {
+        Some(schedule) => {
+            util::register_to_schedule_in_timezone(
+                &schedule.cron,
+                schedule.timezone,
+                &mut scheduler,
+                command,
+            )
+            .await?
+        }
+        None => {
+            util::register_to_schedule_in_timezone(
+                &options.messages_cron,
+                Local,
+                &mut scheduler,
+                command,
+            )
+            .await?
+        }
+    };
+    log::info!("Registered daily messages for {chat_id}");
 
     if let Some(old_job_id) = sched_storage_rw
         .as_ref()
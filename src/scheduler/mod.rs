@@ -1,28 +1,30 @@
 use crate::telegram_bot::TelegramControlCommand;
 use crate::{codeforces, options};
 use async_cron_scheduler::{JobId, Scheduler};
-use chrono::Local;
+use chrono::Utc;
 use miette::{IntoDiagnostic, Result, GraphicalReportHandler};
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use teloxide::prelude::*;
 use tokio::sync::{mpsc, RwLock};
 use tokio_graceful_shutdown::SubsystemHandle;
 
+mod active_chats;
 mod controller;
 mod daily_message;
 mod updater;
 mod util;
 
+use active_chats::ActiveChats;
 pub use controller::SchedulerControlCommand;
 
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
-pub(self) struct SchedulerStorage {
+struct SchedulerStorage {
     daily_message_job_ids: HashMap<ChatId, JobId>,
-    update_message_job_ids: HashMap<ChatId, JobId>,
+    updated_chat_ids: HashSet<ChatId>,
 }
-type MyScheduler = Scheduler<Local>;
+type MyScheduler = Scheduler<Utc>;
 
 pub async fn subsystem_handler(
     options: Arc<options::Options>,
@@ -41,15 +43,31 @@ pub async fn subsystem_handler(
     // make data sharable
     let telegram_send_arc = Arc::new(telegram_send);
     let storage_arc = Arc::new(RwLock::new(SchedulerStorage::default()));
+    let active_chats_arc = Arc::new(ActiveChats::open(options.as_ref()).await?);
+    updater::start(
+        storage_arc.clone(),
+        scheduler_arc.clone(),
+        telegram_send_arc.clone(),
+        cf_client.clone(),
+    )
+    .await?;
 
     log::info!("Set up scheduler service");
 
     let mut open_tasks = Vec::new();
     let spawn_task = |command| {
-        let (options_clone, storage_clone, scheduler_clone, telegram_send_clone, cf_client_clone) = (
+        let (
+            options_clone,
+            storage_clone,
+            scheduler_clone,
+            active_chats_clone,
+            telegram_send_clone,
+            cf_client_clone,
+        ) = (
             options.clone(),
             storage_arc.clone(),
             scheduler_arc.clone(),
+            active_chats_arc.clone(),
             telegram_send_arc.clone(),
             cf_client.clone(),
         );
@@ -59,6 +77,7 @@ pub async fn subsystem_handler(
                 options_clone,
                 storage_clone,
                 scheduler_clone,
+                active_chats_clone,
                 telegram_send_clone,
                 cf_client_clone,
             )
@@ -76,6 +95,11 @@ pub async fn subsystem_handler(
             }
         })
     };
+    // restore jobs of chats that were active before the last shutdown
+    for chat_id in active_chats_arc.all().await? {
+        open_tasks.push(spawn_task(SchedulerControlCommand::RestoreDailyMessages { chat_id }));
+    }
+
     // main control loop
     loop {
         tokio::select! {
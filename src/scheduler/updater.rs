@@ -1,28 +1,54 @@
 use crate::codeforces;
 use crate::scheduler::{util, MyScheduler, SchedulerStorage};
-use crate::telegram_bot::TelegramControlCommand;
 use crate::telegram_bot::TelegramControlCommand::UpdateSolvingStatus;
+use crate::telegram_bot::{ChannelState, TelegramControlCommand};
 use futures::stream::{self, StreamExt};
 use miette::{IntoDiagnostic, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use teloxide::prelude::ChatId;
 use tokio::sync::{mpsc, RwLock};
 
 const CRON_SCHEDULE: &str = "30 0/5 * * * * *";
 
+/// Fetches the submissions of every handle registered in any updated chat once,
+/// and sends each chat the solving status of its registered users
 async fn update(
-    chat_id: ChatId,
+    sched_storage_rw: &RwLock<SchedulerStorage>,
     telegram_send: &mpsc::UnboundedSender<TelegramControlCommand>,
     cf_client: &codeforces::Client,
 ) -> Result<()> {
-    let channel_state = crate::util::get_channel_state(chat_id, telegram_send).await?;
+    let chat_ids: Vec<ChatId> = sched_storage_rw
+        .read()
+        .await
+        .updated_chat_ids
+        .iter()
+        .copied()
+        .collect();
+
+    let channel_states: Vec<(ChatId, ChannelState)> = stream::iter(chat_ids)
+        .filter_map(|chat_id| async move {
+            match crate::util::get_channel_state(chat_id, telegram_send).await {
+                Ok(channel_state) => Some((chat_id, channel_state)),
+                Err(report) => {
+                    log::error!("Error getting channel state for {:?}\n{}", chat_id, report);
+                    None
+                }
+            }
+        })
+        .collect()
+        .await;
+
+    let handles: HashSet<&codeforces::Handle> = channel_states
+        .iter()
+        .flat_map(|(_, channel_state)| channel_state.registered_users().map(|(_, handle)| handle))
+        .collect();
 
-    let submissions_per_handle: HashMap<codeforces::Handle, Vec<codeforces::Submission>> =
-        stream::iter(channel_state.registered_users().values())
+    let summaries: HashMap<&codeforces::Handle, codeforces::SubmissionSummary> =
+        stream::iter(handles)
             .filter_map(|handle| async move {
-                match handle.get_submissions(cf_client).await {
-                    Ok(submissions) => Some((handle.clone(), submissions)),
+                match cf_client.submission_summary(handle).await {
+                    Ok(summary) => Some((handle, summary)),
                     Err(report) => {
                         log::error!(
                             "Error getting submissions for {}\n{}",
@@ -36,54 +62,57 @@ async fn update(
             .collect()
             .await;
 
-    let status_per_problem = {
+    for (chat_id, channel_state) in &channel_states {
         let mut status_per_problem: HashMap<
             codeforces::ProblemIdentifier,
             HashMap<codeforces::Handle, codeforces::VerdictCategory>,
         > = HashMap::new();
 
-        for (handle, submissions) in submissions_per_handle {
-            for submission in submissions {
-                if let Some(verdict) = submission.verdict {
-                    status_per_problem
-                        .entry(submission.problem.identifier()?)
-                        .or_insert_with(Default::default)
-                        .entry(handle.clone())
-                        .and_modify(|previous_category| {
-                            *previous_category = Ord::max(*previous_category, verdict.category());
-                        })
-                        .or_insert_with(|| verdict.category());
-                }
+        // only the problems shown in daily messages are rendered
+        let daily_problem_ids = channel_state.daily_problem_ids();
+        for (_, handle) in channel_state.registered_users() {
+            let verdicts = summaries
+                .get(handle)
+                .map(codeforces::SubmissionSummary::verdict_per_problem);
+            for (problem_id, verdict_category) in verdicts
+                .into_iter()
+                .flatten()
+                .filter(|(problem_id, _)| daily_problem_ids.contains(*problem_id))
+            {
+                status_per_problem
+                    .entry(problem_id.clone())
+                    .or_default()
+                    .insert(handle.clone(), *verdict_category);
             }
         }
 
-        status_per_problem
-    };
+        telegram_send
+            .send(UpdateSolvingStatus {
+                chat_id: *chat_id,
+                status: status_per_problem,
+            })
+            .into_diagnostic()?;
+    }
 
-    telegram_send
-        .send(UpdateSolvingStatus {
-            chat_id,
-            status: status_per_problem,
-        })
-        .into_diagnostic()
+    Ok(())
 }
 
+/// Registers the poller shared by all chats
 pub(super) async fn start(
-    chat_id: ChatId,
     sched_storage_rw: Arc<RwLock<SchedulerStorage>>,
     scheduler_rw: Arc<RwLock<MyScheduler>>,
     telegram_send: Arc<mpsc::UnboundedSender<TelegramControlCommand>>,
     cf_client: Arc<codeforces::Client>,
 ) -> Result<()> {
-    log::info!("Registered updater for {chat_id}");
     let mut scheduler = scheduler_rw.as_ref().write().await;
 
-    let job_id = util::register_to_schedule(CRON_SCHEDULE, &mut scheduler, move |_id| {
+    util::register_to_schedule(CRON_SCHEDULE, &mut scheduler, move |_id| {
+        let sched_storage_clone = sched_storage_rw.clone();
         let telegram_send_clone = telegram_send.clone();
         let cf_client_clone = cf_client.clone();
         tokio::spawn(async move {
             update(
-                chat_id,
+                sched_storage_clone.as_ref(),
                 telegram_send_clone.as_ref(),
                 cf_client_clone.as_ref(),
             )
@@ -92,16 +121,29 @@ pub(super) async fn start(
         });
     })
     .await?;
+    log::info!("Registered updater");
 
-    if let Some(old_job_id) = sched_storage_rw
+    Ok(())
+}
+
+pub(super) async fn add_chat(chat_id: ChatId, sched_storage_rw: Arc<RwLock<SchedulerStorage>>) {
+    log::info!("Registered updates for {chat_id}");
+    sched_storage_rw
+        .as_ref()
+        .write()
+        .await
+        .updated_chat_ids
+        .insert(chat_id);
+}
+
+pub(super) async fn remove_chat(chat_id: ChatId, sched_storage_rw: Arc<RwLock<SchedulerStorage>>) {
+    if sched_storage_rw
         .as_ref()
         .write()
         .await
-        .update_message_job_ids
-        .insert(chat_id, job_id)
+        .updated_chat_ids
+        .remove(&chat_id)
     {
-        scheduler.remove(old_job_id);
+        log::info!("Removed updates for {chat_id}");
     }
-
-    Ok(())
 }
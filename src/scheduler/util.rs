@@ -1,6 +1,13 @@
 use crate::scheduler::MyScheduler;
+use async_cron_scheduler::cron::Schedule;
 use async_cron_scheduler::{Job, JobId};
-use miette::{IntoDiagnostic, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use miette::{miette, IntoDiagnostic, Result};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Cron schedule of the ticker driving jobs registered with [`register_to_schedule_in_timezone`]
+const TICKER_CRON_SCHEDULE: &str = "0 * * * * * *";
 
 pub(super) async fn register_to_schedule(
     cron_str: &str,
@@ -11,3 +18,41 @@ pub(super) async fn register_to_schedule(
     Ok(scheduler.insert(job, command))
 }
 
+/// Registers a job whose cron schedule is evaluated in `timezone` instead of the scheduler's timezone.
+///
+/// The scheduler only supports a single timezone, so the job is driven by a minutely ticker that
+/// checks whether the next occurrence in `timezone` has been reached.
+/// Hence, the command is run with up to a minute delay.
+pub(super) async fn register_to_schedule_in_timezone<Z>(
+    cron_str: &str,
+    timezone: Z,
+    scheduler: &mut MyScheduler,
+    command: impl Fn(JobId) + Send + Sync + 'static,
+) -> Result<JobId>
+where
+    Z: TimeZone + Send + Sync + 'static,
+    Z::Offset: Send + Sync,
+{
+    let schedule = Schedule::from_str(cron_str).into_diagnostic()?;
+    let next_occurrence = move |after: DateTime<Utc>| {
+        schedule
+            .after(&after.with_timezone(&timezone))
+            .next()
+            .map(|date_time| date_time.with_timezone(&Utc))
+    };
+    let next = Mutex::new(
+        next_occurrence(Utc::now())
+            .ok_or_else(|| miette!("Schedule {cron_str:?} never triggers"))?,
+    );
+
+    register_to_schedule(TICKER_CRON_SCHEDULE, scheduler, move |id| {
+        let mut next = next.lock().expect("Lock on next occurrence is poisoned");
+        // the ticker may fire slightly early, so allow for some tolerance
+        let now = Utc::now() + Duration::seconds(1);
+        if now >= *next {
+            *next = next_occurrence(Ord::max(now, *next)).unwrap_or(DateTime::<Utc>::MAX_UTC);
+            command(id);
+        }
+    })
+    .await
+}
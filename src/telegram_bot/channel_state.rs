@@ -1,112 +1,1392 @@
 use crate::codeforces::{self, Problem};
+use chrono::{Datelike, Days, Local, NaiveDate, Utc, Weekday};
 use futures::StreamExt;
-use miette::{IntoDiagnostic, Result};
-use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
-use std::collections::hash_map::DefaultHasher;
-use std::collections::{HashMap, HashSet};
-use std::hash::{Hash, Hasher};
+use miette::{miette, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{btree_map, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::ops::RangeInclusive;
-use std::time::{SystemTime, UNIX_EPOCH};
 use teloxide::prelude::*;
+use teloxide::types::MessageId;
 use xorshift::{Rng, SeedableRng, Xorshift128};
 
 const DEFAULT_RATING_RANGE: RangeInclusive<u64> = 2000..=2400;
+/// Highest rating of Codeforces problems, beyond which widening the rating range finds nothing new
+const MAX_PROBLEM_RATING: u64 = 3500;
+/// Share of the admissible problems, by solved count, a problem is chosen from if a popularity is preferred
+const PREFERRED_SHARE: usize = 4;
+/// Days between the reviews of a failed problem, starting when it was first found failed
+const REVIEW_INTERVAL_DAYS: [u64; 4] = [3, 7, 14, 30];
+/// Number of queued problems listed by [`ChannelState::queue_text`]
+const QUEUE_TEXT_LENGTH: usize = 30;
+pub const DEFAULT_TAG_WEIGHT: u64 = 1;
+/// Highest weight a tag can be given, so the weights of all tags can be summed up
+pub const MAX_TAG_WEIGHT: u64 = 1_000_000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MessageSchedule {
+    pub cron: String,
+    pub timezone: chrono_tz::Tz,
+}
+
+/// How the rating range is widened if it contains no admissible problem
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RatingWidening {
+    /// Rating by which both bounds are moved per step
+    pub step: u64,
+    pub max_steps: u64,
+}
+
+/// Rating range derived from the ratings of the registered users
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AutoRatingRange {
+    /// Offset of the center of the range from the median rating
+    pub offset: i64,
+    pub width: u64,
+}
+
+/// Selection rule of a planned daily message, which is tried before the regular selection
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ThemeRule {
+    /// If not empty, only these tags are drawn
+    pub tags: BTreeSet<String>,
+    /// Replaces the rating range of the chat, the ranges of difficulty tiers are kept
+    pub rating_range: Option<RangeInclusive<u64>>,
+}
+
+/// Themes of the daily messages on certain weekdays or dates, where dates take precedence
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ThemePlan {
+    pub weekdays: HashMap<Weekday, ThemeRule>,
+    pub dates: BTreeMap<NaiveDate, ThemeRule>,
+}
+
+/// Restrictions on the contests daily problems are taken from
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ContestFilter {
+    /// If not empty, only problems of contests of these kinds are chosen
+    pub kinds: BTreeSet<codeforces::ContestKind>,
+    pub min_year: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Popularity {
+    #[default]
+    Any,
+    /// Problems solved by many
+    Classic,
+    /// Problems solved by few
+    Obscure,
+}
+
+/// Preferences on how many people solved the daily problems
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PopularityPreferences {
+    pub min_solved_count: Option<u64>,
+    pub max_solved_count: Option<u64>,
+    pub preferred: Popularity,
+}
+
+/// Daily problems are taken from recent contests registered users took part in
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Upsolving {
+    /// Number of registered users that need to have taken part in a contest
+    pub min_participants: usize,
+    /// Contests are considered if users took part in them within this many days
+    pub max_age_days: u64,
+}
+
+/// Which problems count as known to the registered users and are not chosen
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KnownProblemPolicy {
+    /// Problems any registered user submitted to
+    #[default]
+    Attempted,
+    /// Problems any registered user solved
+    Solved,
+    /// Problems solved by at least this many registered users
+    SolvedBy(usize),
+}
+
+/// How many votes are needed within which time to replace the current daily problem
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RerollSettings {
+    pub quorum: usize,
+    pub minutes: u64,
+}
+
+/// Running vote on replacing the current daily problem
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RerollVote {
+    /// Message with the voting button
+    pub message_id: MessageId,
+    /// Daily message the vote is about replacing, votes stored without it match no message
+    #[serde(default = "unknown_message_id")]
+    pub daily_message_id: MessageId,
+    pub voters: BTreeSet<UserId>,
+    /// Unix time in seconds at which the vote was started
+    pub started_at: i64,
+}
+
+/// Whether and where failed daily problems are sent again for review
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewMode {
+    #[default]
+    Off,
+    /// Reviews are posted in the chat
+    Chat,
+    /// Reviews are sent as direct messages to the users who failed the problem
+    #[serde(rename = "dm", alias = "direct")]
+    Direct,
+}
+
+/// Archived daily problem which registered users tried but did not solve
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ReviewItem {
+    pub failed_handles: BTreeSet<codeforces::Handle>,
+    /// Number of reviews sent so far
+    pub repetitions: usize,
+    /// `None` once all reviews have been sent
+    pub next_review: Option<NaiveDate>,
+}
+
+/// How long the tags of daily problems are avoided afterwards
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct TagRotation {
+    /// Number of daily messages whose tags are drawn last and avoided as other tags, 0 disables the rotation
+    pub days: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct TagPreferences {
+    /// If not empty, only these tags are drawn
+    pub included: BTreeSet<String>,
+    /// Problems with any of these tags are never chosen
+    pub excluded: BTreeSet<String>,
+    /// Relative weights for drawing tags, [`DEFAULT_TAG_WEIGHT`] if not given
+    pub weights: BTreeMap<String, u64>,
+}
+
+/// Named rating range, for which a separate problem is drawn every day
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DifficultyTier {
+    pub name: String,
+    pub rating_range: RangeInclusive<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DailyProblem {
+    /// Name of the tier the problem was drawn for, `None` if the chat has no tiers
+    pub tier: Option<String>,
+    pub problem_id: codeforces::ProblemIdentifier,
+}
+
+/// Sent daily message together with the problems it shows, in tier order
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DailyMessage {
+    pub problems: Vec<DailyProblem>,
+    pub message: Message,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RegisteredUser {
+    pub display_name: String,
+    pub handle: codeforces::Handle,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PendingVerification {
+    pub display_name: String,
+    pub handle: codeforces::Handle,
+    pub problem: codeforces::Problem,
+    /// Unix time in seconds at which the verification was requested
+    pub requested_at: i64,
+}
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ChannelState {
-    pub(super) registered_users: HashMap<String, codeforces::Handle>,
+    #[serde(rename = "registered_users_by_id", default)]
+    pub(super) registered_users: HashMap<UserId, RegisteredUser>,
+    /// Users registered by display name, before registrations were bound to Telegram users
+    #[serde(
+        rename = "registered_users",
+        default,
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub(super) legacy_registered_users: HashMap<String, codeforces::Handle>,
+    #[serde(default)]
+    pub(super) pending_verifications: HashMap<UserId, PendingVerification>,
     pub(super) rating_range: Option<RangeInclusive<u64>>,
-    pub(super) current_daily_problem: Option<codeforces::Problem>,
-    pub(super) current_daily_message: Option<Message>,
-    pub(super) archived_daily_messages: HashMap<codeforces::ProblemIdentifier, Vec<Message>>,
+    #[serde(default)]
+    pub(super) auto_rating_range: Option<AutoRatingRange>,
+    #[serde(default)]
+    pub(super) rating_widening: RatingWidening,
+    #[serde(default)]
+    pub(super) tag_preferences: TagPreferences,
+    #[serde(default)]
+    pub(super) tag_rotation: TagRotation,
+    #[serde(default)]
+    pub(super) contest_filter: ContestFilter,
+    #[serde(default)]
+    pub(super) popularity: PopularityPreferences,
+    #[serde(default)]
+    pub(super) known_problem_policy: KnownProblemPolicy,
+    #[serde(default)]
+    pub(super) reroll_settings: RerollSettings,
+    #[serde(default)]
+    pub(super) reroll_vote: Option<RerollVote>,
+    #[serde(default)]
+    pub(super) upsolving: Option<Upsolving>,
+    #[serde(default)]
+    pub(super) review_mode: ReviewMode,
+    #[serde(default)]
+    pub(super) review_items: BTreeMap<codeforces::ProblemIdentifier, ReviewItem>,
+    #[serde(default)]
+    pub(super) theme_plan: ThemePlan,
+    /// Problems to send before any are drawn, whose details are in `problem_by_identifier`
+    #[serde(default)]
+    pub(super) problem_queue: VecDeque<codeforces::ProblemIdentifier>,
+    /// Tags of the problems of the most recent daily messages, newest last
+    #[serde(default)]
+    pub(super) recent_tags: VecDeque<BTreeSet<String>>,
+    #[serde(default)]
+    pub(super) difficulty_tiers: Vec<DifficultyTier>,
+    #[serde(default, deserialize_with = "deserialize_current_daily_message")]
+    pub(super) current_daily_message: Option<DailyMessage>,
+    /// Problem of the current daily message, before messages could show several problems
+    #[serde(
+        rename = "current_daily_problem",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(super) legacy_current_daily_problem: Option<codeforces::Problem>,
+    #[serde(default, deserialize_with = "deserialize_archived_daily_messages")]
+    pub(super) archived_daily_messages: Vec<DailyMessage>,
     pub(super) problem_by_identifier: HashMap<codeforces::ProblemIdentifier, Problem>,
+    pub(super) message_schedule: Option<MessageSchedule>,
+    #[serde(default)]
+    pub(super) paused: bool,
+    /// Users which may change the configuration without being chat admins
+    #[serde(default)]
+    pub(super) allowed_users: HashSet<UserId>,
+}
+
+fn unknown_message_id() -> MessageId {
+    MessageId(0)
+}
+
+/// 64-bit FNV-1a hash, which unlike the hashers of the standard library is stable across Rust versions
+fn stable_hash(value: &str) -> u64 {
+    value.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Derives the two state words of a random number generator from a single stable hash,
+/// mixing the second one with the finalizer of SplitMix64
+fn stable_seed(value: &str) -> [u64; 2] {
+    let hash = stable_hash(value);
+    let mut mixed = hash.wrapping_add(0x9e3779b97f4a7c15);
+    mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d049bb133111eb);
+    [hash, mixed ^ (mixed >> 31)]
+}
+
+/// Keeps the problems sharing the fewest tags with the recent daily problems,
+/// so topics do not repeat through the other tags of a problem
+fn least_recent<'a>(
+    mut problems: Vec<&'a codeforces::IndexedProblem>,
+    recent_tags: &HashSet<String>,
+) -> Vec<&'a codeforces::IndexedProblem> {
+    let recent_count = |indexed_problem: &codeforces::IndexedProblem| {
+        indexed_problem
+            .problem
+            .tags
+            .iter()
+            .filter(|tag| recent_tags.contains(*tag))
+            .count()
+    };
+    if let Some(min_recent_count) = problems.iter().map(|problem| recent_count(problem)).min() {
+        problems.retain(|problem| recent_count(problem) == min_recent_count);
+    }
+    problems
+}
+
+/// Reads the current daily message, also if it was stored without its problems
+fn deserialize_current_daily_message<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<DailyMessage>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Current(DailyMessage),
+        // the problems are taken from `legacy_current_daily_problem`
+        Legacy(Message),
+    }
+
+    Ok(
+        Option::<Stored>::deserialize(deserializer)?.map(|stored| match stored {
+            Stored::Current(daily_message) => daily_message,
+            Stored::Legacy(message) => DailyMessage {
+                problems: Vec::new(),
+                message,
+            },
+        }),
+    )
+}
+
+/// Reads the archived daily messages, also if they are stored by problem
+fn deserialize_archived_daily_messages<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<DailyMessage>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Current(Vec<DailyMessage>),
+        Legacy(HashMap<codeforces::ProblemIdentifier, Vec<Message>>),
+    }
+
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::Current(daily_messages) => daily_messages,
+        Stored::Legacy(messages_by_problem) => messages_by_problem
+            .into_iter()
+            .flat_map(|(problem_id, messages)| {
+                messages.into_iter().map(move |message| DailyMessage {
+                    problems: vec![DailyProblem {
+                        tier: None,
+                        problem_id: problem_id.clone(),
+                    }],
+                    message,
+                })
+            })
+            .collect(),
+    })
+}
+
+impl Default for RatingWidening {
+    fn default() -> Self {
+        Self {
+            step: 100,
+            max_steps: 2,
+        }
+    }
+}
+
+impl ThemeRule {
+    pub fn text(&self) -> String {
+        let tags = if self.tags.is_empty() {
+            String::from("any tag")
+        } else {
+            self.tags.iter().cloned().collect::<Vec<_>>().join(", ")
+        };
+        match &self.rating_range {
+            Some(rating_range) => format!(
+                "{}, rating {} to {}",
+                tags,
+                rating_range.start(),
+                rating_range.end()
+            ),
+            None => tags,
+        }
+    }
+}
+
+impl ContestFilter {
+    pub fn text(&self) -> String {
+        format!(
+            "Contest kinds: {}\nMinimum contest year: {}",
+            if self.kinds.is_empty() {
+                String::from("-")
+            } else {
+                self.kinds
+                    .iter()
+                    .map(|kind| kind.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            },
+            self.min_year
+                .map_or_else(|| String::from("-"), |min_year| min_year.to_string())
+        )
+    }
+}
+
+impl Popularity {
+    pub const ALL: [Popularity; 3] = [Popularity::Any, Popularity::Classic, Popularity::Obscure];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Popularity::Any => "any",
+            Popularity::Classic => "classic",
+            Popularity::Obscure => "obscure",
+        }
+    }
+}
+
+impl PopularityPreferences {
+    pub fn solved_count(&self) -> Option<RangeInclusive<u64>> {
+        if self.min_solved_count.is_none() && self.max_solved_count.is_none() {
+            None
+        } else {
+            Some(self.min_solved_count.unwrap_or(0)..=self.max_solved_count.unwrap_or(u64::MAX))
+        }
+    }
+
+    /// Narrows admissible problems down to the most or least solved ones, if preferred
+    pub fn candidates<'a>(
+        &self,
+        mut problems: Vec<&'a codeforces::IndexedProblem>,
+    ) -> Vec<&'a codeforces::IndexedProblem> {
+        let by_identifier = |a: &codeforces::IndexedProblem, b: &codeforces::IndexedProblem| {
+            (a.problem.contest_id, &a.problem.index).cmp(&(b.problem.contest_id, &b.problem.index))
+        };
+        // ties are broken by identifier, so the order does not depend on the sorting algorithm
+        match self.preferred {
+            Popularity::Any => return problems,
+            Popularity::Classic => problems.sort_by(|a, b| {
+                b.solved_count
+                    .cmp(&a.solved_count)
+                    .then_with(|| by_identifier(a, b))
+            }),
+            Popularity::Obscure => problems.sort_by(|a, b| {
+                a.solved_count
+                    .cmp(&b.solved_count)
+                    .then_with(|| by_identifier(a, b))
+            }),
+        }
+        problems.truncate(Ord::max(1, problems.len() / PREFERRED_SHARE));
+        problems
+    }
+
+    pub fn text(&self) -> String {
+        let format_bound =
+            |bound: Option<u64>| bound.map_or_else(|| String::from("-"), |bound| bound.to_string());
+        format!(
+            "Minimum solved count: {}\nMaximum solved count: {}\nPreferred problems: {}",
+            format_bound(self.min_solved_count),
+            format_bound(self.max_solved_count),
+            self.preferred.name()
+        )
+    }
+}
+
+impl Upsolving {
+    pub const MAX_AGE_DAYS: u64 = 365;
+}
+
+impl KnownProblemPolicy {
+    /// Describes the problems that are not known, to complete "Could not find a problem"
+    pub fn unknown_text(&self) -> String {
+        match self {
+            KnownProblemPolicy::Attempted => String::from("that no registered user has tried yet"),
+            KnownProblemPolicy::Solved => String::from("that no registered user has solved yet"),
+            KnownProblemPolicy::SolvedBy(min_solvers) => {
+                format!(
+                    "that fewer than {} registered users have solved",
+                    min_solvers
+                )
+            }
+        }
+    }
+}
+
+impl ReviewMode {
+    pub const ALL: [ReviewMode; 3] = [ReviewMode::Off, ReviewMode::Chat, ReviewMode::Direct];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReviewMode::Off => "off",
+            ReviewMode::Chat => "chat",
+            ReviewMode::Direct => "dm",
+        }
+    }
+}
+
+impl ThemePlan {
+    pub fn theme_for(&self, date: NaiveDate) -> Option<&ThemeRule> {
+        self.dates
+            .get(&date)
+            .or_else(|| self.weekdays.get(&date.weekday()))
+    }
+
+    pub fn text(&self) -> String {
+        if self.weekdays.is_empty() && self.dates.is_empty() {
+            return String::from("There are no planned themes");
+        }
+
+        let mut weekdays: Vec<_> = self.weekdays.iter().collect();
+        weekdays.sort_unstable_by_key(|(weekday, _)| weekday.num_days_from_monday());
+
+        let mut result = String::from("Planned themes:\n");
+        for (weekday, theme) in weekdays {
+            result.push_str(&format!("{}: {}\n", weekday, theme.text()));
+        }
+        for (date, theme) in &self.dates {
+            result.push_str(&format!("{}: {}\n", date.format("%Y-%m-%d"), theme.text()));
+        }
+        result.trim_end().into()
+    }
+}
+
+impl Default for TagRotation {
+    fn default() -> Self {
+        Self { days: 3 }
+    }
+}
+
+impl Default for RerollSettings {
+    fn default() -> Self {
+        Self {
+            quorum: 3,
+            minutes: 60,
+        }
+    }
+}
+
+impl RerollSettings {
+    pub const MAX_MINUTES: u64 = 24 * 60;
+
+    pub fn duration_s(&self) -> u64 {
+        self.minutes.saturating_mul(60)
+    }
+}
+
+impl RerollVote {
+    pub fn is_expired(&self, settings: &RerollSettings, unix_time_s: i64) -> bool {
+        let duration_s = i64::try_from(settings.duration_s()).unwrap_or(i64::MAX);
+        unix_time_s > self.started_at.saturating_add(duration_s)
+    }
+}
+
+impl RatingWidening {
+    pub const MAX_STEP: u64 = 1000;
+    pub const MAX_STEPS: u64 = 20;
+
+    pub fn widen(&self, rating_range: &RangeInclusive<u64>, steps: u64) -> RangeInclusive<u64> {
+        let widening = self.step.saturating_mul(steps);
+        rating_range.start().saturating_sub(widening)..=rating_range.end().saturating_add(widening)
+    }
+
+    /// Whether widening the given range any further cannot find other problems
+    pub fn is_exhausted(&self, rating_range: &RangeInclusive<u64>) -> bool {
+        self.step == 0 || (*rating_range.start() == 0 && *rating_range.end() >= MAX_PROBLEM_RATING)
+    }
+}
+
+impl TagPreferences {
+    pub fn weight(&self, tag: &str) -> u64 {
+        if self.excluded.contains(tag) || !(self.included.is_empty() || self.included.contains(tag))
+        {
+            0
+        } else {
+            self.weights.get(tag).copied().unwrap_or(DEFAULT_TAG_WEIGHT)
+        }
+    }
+
+    /// Orders the drawable tags randomly, such that tags with a higher weight tend to come first.
+    ///
+    /// Recently used tags are only drawn after all other tags.
+    pub fn draw_order(
+        &self,
+        rng: &mut impl Rng,
+        recent_tags: &HashSet<String>,
+    ) -> Vec<&'static str> {
+        let (mut recent, mut remaining): (Vec<_>, Vec<_>) = codeforces::TAGS
+            .iter()
+            .map(|tag| (*tag, self.weight(tag)))
+            .filter(|(_, weight)| *weight > 0)
+            .partition(|(tag, _)| recent_tags.contains(*tag));
+
+        let mut order = Vec::with_capacity(remaining.len() + recent.len());
+        for remaining in [&mut remaining, &mut recent] {
+            while !remaining.is_empty() {
+                let total_weight = remaining
+                    .iter()
+                    .fold(0, |total, (_, weight)| u64::saturating_add(total, *weight));
+                let mut drawn = rng.next_u64() % total_weight;
+                let index = remaining
+                    .iter()
+                    .position(|(_, weight)| {
+                        if drawn < *weight {
+                            true
+                        } else {
+                            drawn -= weight;
+                            false
+                        }
+                    })
+                    .expect("Drawn weight is smaller than the total weight");
+                order.push(remaining.swap_remove(index).0);
+            }
+        }
+        order
+    }
+
+    pub fn text(&self) -> String {
+        let join = |tags: &BTreeSet<String>| {
+            if tags.is_empty() {
+                String::from("-")
+            } else {
+                tags.iter().cloned().collect::<Vec<_>>().join(", ")
+            }
+        };
+        let weights = if self.weights.is_empty() {
+            String::from("-")
+        } else {
+            self.weights
+                .iter()
+                .map(|(tag, weight)| format!("{tag}={weight}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!(
+            "Included tags: {}\nExcluded tags: {}\nTag weights: {}",
+            join(&self.included),
+            join(&self.excluded),
+            weights
+        )
+    }
 }
 
 impl ChannelState {
     pub fn rating_range(&self) -> &RangeInclusive<u64> {
         self.rating_range.as_ref().unwrap_or(&DEFAULT_RATING_RANGE)
     }
-    pub fn registered_users(&self) -> &HashMap<String, codeforces::Handle> {
-        &self.registered_users
+    /// Gets the rating range problems are searched in, which follows the users' ratings in auto mode
+    pub async fn target_rating_range(&self, cf_client: &codeforces::Client) -> RangeInclusive<u64> {
+        let Some(auto_rating_range) = &self.auto_rating_range else {
+            return self.rating_range().clone();
+        };
+
+        let handles: BTreeSet<_> = self.registered_users().map(|(_, handle)| handle).collect();
+        if handles.is_empty() {
+            return self.rating_range().clone();
+        }
+        let mut ratings: Vec<u64> = match cf_client.get_users(handles.into_iter()).await {
+            Ok(users) => users.into_iter().filter_map(|user| user.rating).collect(),
+            Err(report) => {
+                log::warn!("Could not get ratings of registered users\n{}", report);
+                Vec::new()
+            }
+        };
+        if ratings.is_empty() {
+            return self.rating_range().clone();
+        }
+        ratings.sort_unstable();
+
+        let median = ratings[ratings.len() / 2] as i64;
+        // problem ratings are multiples of 100
+        let center = (median.saturating_add(auto_rating_range.offset).max(0) as u64)
+            .saturating_add(50)
+            / 100
+            * 100;
+        center.saturating_sub(auto_rating_range.width / 2)
+            ..=center.saturating_add(auto_rating_range.width / 2)
+    }
+    pub fn registered_users(&self) -> impl Iterator<Item = (&str, &codeforces::Handle)> {
+        self.registered_users
+            .values()
+            .map(|user| (user.display_name.as_str(), &user.handle))
+            .chain(
+                self.legacy_registered_users
+                    .iter()
+                    .map(|(display_name, handle)| (display_name.as_str(), handle)),
+            )
+    }
+    pub fn message_schedule(&self) -> &Option<MessageSchedule> {
+        &self.message_schedule
+    }
+    /// Current date in the timezone of the daily messages
+    pub fn today(&self) -> NaiveDate {
+        match &self.message_schedule {
+            Some(schedule) => Utc::now().with_timezone(&schedule.timezone).date_naive(),
+            None => Local::now().date_naive(),
+        }
+    }
+    /// Whether the current daily message was posted today, in the timezone of the daily messages
+    pub fn posted_daily_message_today(&self) -> bool {
+        let Some(daily_message) = &self.current_daily_message else {
+            return false;
+        };
+        let posted_on = match &self.message_schedule {
+            Some(schedule) => daily_message
+                .message
+                .date
+                .with_timezone(&schedule.timezone)
+                .date_naive(),
+            None => daily_message
+                .message
+                .date
+                .with_timezone(&Local)
+                .date_naive(),
+        };
+        posted_on == self.today()
     }
-    #[allow(dead_code)]
-    pub fn current_daily_problem(&self) -> &Option<codeforces::Problem> {
-        &self.current_daily_problem
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    /// Fills in the problem of a current daily message stored before messages could show several problems
+    pub fn migrate_current_daily_message(&mut self) -> Result<()> {
+        if let (Some(daily_message), Some(problem)) = (
+            &mut self.current_daily_message,
+            self.legacy_current_daily_problem.take(),
+        ) {
+            if daily_message.problems.is_empty() {
+                daily_message.problems.push(DailyProblem {
+                    tier: None,
+                    problem_id: problem.identifier()?,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Identifiers of all problems shown in the current or archived daily messages
+    pub fn daily_problem_ids(&self) -> HashSet<codeforces::ProblemIdentifier> {
+        let legacy_problem_id = self
+            .legacy_current_daily_problem
+            .as_ref()
+            .and_then(|problem| problem.identifier().ok());
+        self.current_daily_message
+            .iter()
+            .chain(self.archived_daily_messages.iter())
+            .flat_map(|daily_message| daily_message.problems.iter())
+            .map(|daily_problem| daily_problem.problem_id.clone())
+            .chain(legacy_problem_id)
+            .collect()
+    }
+
+    /// Remembers the tags of a new daily message, forgetting those outside the rotation window
+    pub fn record_daily_tags(&mut self, tags: BTreeSet<String>) {
+        self.recent_tags.push_back(tags);
+        self.trim_recent_tags();
+    }
+
+    pub fn set_tag_rotation(&mut self, tag_rotation: TagRotation) {
+        self.tag_rotation = tag_rotation;
+        self.trim_recent_tags();
     }
 
+    fn trim_recent_tags(&mut self) {
+        while self.recent_tags.len() as u64 > self.tag_rotation.days {
+            self.recent_tags.pop_front();
+        }
+    }
+
+    /// Tags used by the daily messages within the rotation window
+    pub fn recent_tags(&self) -> HashSet<String> {
+        self.recent_tags.iter().flatten().cloned().collect()
+    }
+
+    pub fn tags_text(&self) -> String {
+        let mut recent_tags: Vec<_> = self.recent_tags().into_iter().collect();
+        recent_tags.sort_unstable();
+        format!(
+            "{}\nTag rotation: tags of the last {} daily messages are avoided\nRecent tags: {}\nOnly problems {} are chosen",
+            self.tag_preferences.text(),
+            self.tag_rotation.days,
+            if recent_tags.is_empty() {
+                String::from("-")
+            } else {
+                recent_tags.join(", ")
+            },
+            self.known_problem_policy.unknown_text()
+        )
+    }
+
+    pub fn current_daily_message_id(&self) -> Option<MessageId> {
+        self.current_daily_message
+            .as_ref()
+            .map(|daily_message| daily_message.message.id)
+    }
+
+    /// Gets the reroll vote that has not expired yet and is about the current daily message
+    pub fn running_reroll_vote(&self, unix_time_s: i64) -> Option<&RerollVote> {
+        self.reroll_vote.as_ref().filter(|vote| {
+            !vote.is_expired(&self.reroll_settings, unix_time_s)
+                && Some(vote.daily_message_id) == self.current_daily_message_id()
+        })
+    }
+
+    /// Describes why a vote that is not running any more did not replace the daily problem
+    pub fn reroll_vote_over_text(&self, vote: &RerollVote) -> String {
+        if Some(vote.daily_message_id) == self.current_daily_message_id() {
+            format!(
+                "Today's problem stays, the reroll vote ended with {} of {} votes",
+                vote.voters.len(),
+                self.reroll_settings.quorum
+            )
+        } else {
+            String::from("This reroll vote is over, there is a new daily problem")
+        }
+    }
+
+    pub fn reroll_vote_text(&self, vote: &RerollVote) -> String {
+        format!(
+            "Replace today's problem? {} of {} votes within {} minutes",
+            vote.voters.len(),
+            self.reroll_settings.quorum,
+            self.reroll_settings.minutes
+        )
+    }
+
+    pub fn review_mode(&self) -> ReviewMode {
+        self.review_mode
+    }
+
+    /// Keeps track of the archived daily problems that registered users failed, for review mode.
+    ///
+    /// Returns whether anything changed.
+    pub fn update_review_items(
+        &mut self,
+        status: &HashMap<
+            codeforces::ProblemIdentifier,
+            HashMap<codeforces::Handle, codeforces::VerdictCategory>,
+        >,
+    ) -> bool {
+        if self.review_mode == ReviewMode::Off {
+            return false;
+        }
+
+        // the current daily problem can still be solved without a review
+        let current_problem_ids: HashSet<_> = self
+            .current_daily_message
+            .iter()
+            .flat_map(|daily_message| daily_message.problems.iter())
+            .map(|daily_problem| &daily_problem.problem_id)
+            .collect();
+        let archived_problem_ids: BTreeSet<_> = self
+            .archived_daily_messages
+            .iter()
+            .flat_map(|daily_message| daily_message.problems.iter())
+            .map(|daily_problem| &daily_problem.problem_id)
+            .filter(|problem_id| !current_problem_ids.contains(problem_id))
+            .cloned()
+            .collect();
+
+        let today = self.today();
+        let mut changed = false;
+        for problem_id in archived_problem_ids {
+            let failed_handles: BTreeSet<_> = status
+                .get(&problem_id)
+                .into_iter()
+                .flatten()
+                .filter(|(_, verdict_category)| {
+                    **verdict_category == codeforces::VerdictCategory::Incorrect
+                })
+                .map(|(handle, _)| handle.clone())
+                .collect();
+            match self.review_items.entry(problem_id) {
+                btree_map::Entry::Occupied(mut entry) => {
+                    if failed_handles.is_empty() {
+                        entry.remove();
+                        changed = true;
+                    } else if entry.get().failed_handles != failed_handles {
+                        entry.get_mut().failed_handles = failed_handles;
+                        changed = true;
+                    }
+                }
+                btree_map::Entry::Vacant(entry) => {
+                    if !failed_handles.is_empty() {
+                        entry.insert(ReviewItem {
+                            failed_handles,
+                            repetitions: 0,
+                            next_review: today.checked_add_days(Days::new(REVIEW_INTERVAL_DAYS[0])),
+                        });
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    /// Takes the most overdue review and schedules its next repetition
+    pub fn take_due_review(&mut self) -> Option<(codeforces::ProblemIdentifier, ReviewItem)> {
+        let today = self.today();
+        let (problem_id, review_item) = self
+            .review_items
+            .iter_mut()
+            .filter(|(_, review_item)| review_item.next_review.is_some_and(|next| next <= today))
+            .min_by_key(|(_, review_item)| review_item.next_review)?;
+
+        let due_review_item = review_item.clone();
+        review_item.repetitions += 1;
+        review_item.next_review = REVIEW_INTERVAL_DAYS
+            .get(review_item.repetitions)
+            .and_then(|interval_days| today.checked_add_days(Days::new(*interval_days)));
+        Some((problem_id.clone(), due_review_item))
+    }
+
+    pub fn review_text(
+        &self,
+        problem_id: &codeforces::ProblemIdentifier,
+        review_item: &ReviewItem,
+    ) -> Result<String> {
+        let problem = self.problem_by_identifier.get(problem_id).ok_or_else(|| {
+            miette!("For a reviewed Problem Identifier there is no known Problem")
+        })?;
+        let mut display_names: Vec<_> = self
+            .registered_users()
+            .filter(|(_, handle)| review_item.failed_handles.contains(*handle))
+            .map(|(display_name, _)| display_name)
+            .collect();
+        display_names.sort_unstable();
+
+        Ok(format!(
+            "Time for a review: {} ({})\nNot solved yet by: {}",
+            problem.name,
+            problem.url()?,
+            display_names.join(", ")
+        ))
+    }
+
+    pub fn queue_text(&self) -> String {
+        if self.problem_queue.is_empty() {
+            return String::from("There are no queued problems");
+        }
+
+        let mut result = format!("Queued problems ({}):\n", self.problem_queue.len());
+        for (position, problem_id) in self
+            .problem_queue
+            .iter()
+            .enumerate()
+            .take(QUEUE_TEXT_LENGTH)
+        {
+            let problem = self.problem_by_identifier.get(problem_id);
+            result.push_str(&format!(
+                "{}. {} {}\n",
+                position + 1,
+                problem.map_or("?", |problem| problem.name.as_str()),
+                problem
+                    .and_then(|problem| problem.url().ok())
+                    .unwrap_or_default()
+            ));
+        }
+        if self.problem_queue.len() > QUEUE_TEXT_LENGTH {
+            result.push_str(&format!(
+                "... and {} more",
+                self.problem_queue.len() - QUEUE_TEXT_LENGTH
+            ));
+        }
+        result.trim_end().into()
+    }
+
+    pub fn tiers_text(&self) -> String {
+        if self.difficulty_tiers.is_empty() {
+            return String::from(
+                "There are no difficulty tiers, one problem from the rating range is drawn",
+            );
+        }
+        let mut result = String::from("Difficulty tiers:\n");
+        for tier in &self.difficulty_tiers {
+            result.push_str(&format!(
+                "{}: {} to {}\n",
+                tier.name,
+                tier.rating_range.start(),
+                tier.rating_range.end()
+            ));
+        }
+        result.trim_end().into()
+    }
+
+    pub fn register_user(
+        &mut self,
+        user_id: UserId,
+        display_name: String,
+        handle: codeforces::Handle,
+    ) {
+        // a user claiming a handle replaces its legacy registration
+        self.legacy_registered_users
+            .retain(|_, legacy_handle| *legacy_handle != handle);
+        self.pending_verifications.remove(&user_id);
+        self.registered_users.insert(
+            user_id,
+            RegisteredUser {
+                display_name,
+                handle,
+            },
+        );
+    }
+
+    /// Gets the problems that are not chosen according to the [`KnownProblemPolicy`],
+    /// from the submission summaries of the registered users
     pub async fn known_problems(
         &self,
         cf_client: &codeforces::Client,
-    ) -> HashSet<codeforces::Problem> {
-        futures::stream::iter(self.registered_users().values())
+    ) -> HashSet<codeforces::ProblemIdentifier> {
+        let handles: BTreeSet<_> = self.registered_users().map(|(_, handle)| handle).collect();
+        let summaries: Vec<codeforces::SubmissionSummary> = futures::stream::iter(handles)
             .filter_map(|handle| async {
-                match handle.get_submissions(cf_client).await {
-                    Ok(submissions) => Some(submissions),
+                match cf_client.submission_summary(handle).await {
+                    Ok(summary) => Some(summary),
                     Err(err) => {
                         log::warn!("Error getting submissions for {}\n{}", handle.as_str(), err);
                         None
                     }
                 }
             })
-            .flat_map(|submissions| {
-                futures::stream::iter(submissions.into_iter().map(|submission| submission.problem))
-            })
             .collect()
-            .await
+            .await;
+
+        let min_solvers = match self.known_problem_policy {
+            KnownProblemPolicy::Attempted => {
+                return summaries
+                    .iter()
+                    .flat_map(|summary| summary.verdict_per_problem().keys().cloned())
+                    .collect();
+            }
+            KnownProblemPolicy::Solved => 1,
+            KnownProblemPolicy::SolvedBy(min_solvers) => min_solvers,
+        };
+
+        let mut solver_counts: HashMap<&codeforces::ProblemIdentifier, usize> = HashMap::new();
+        for (problem_id, verdict_category) in summaries
+            .iter()
+            .flat_map(|summary| summary.verdict_per_problem())
+        {
+            if *verdict_category == codeforces::VerdictCategory::Correct {
+                *solver_counts.entry(problem_id).or_default() += 1;
+            }
+        }
+        solver_counts
+            .into_iter()
+            .filter(|(_, solver_count)| *solver_count >= min_solvers)
+            .map(|(problem_id, _)| problem_id.clone())
+            .collect()
     }
 
-    pub async fn find_daily_problem(
+    /// Searches one problem that is not known per difficulty tier, or a single one
+    /// within the target rating range if the chat has no tiers.
+    ///
+    /// If problems are queued, the first one is taken instead, and else a problem to upsolve if enabled.
+    /// Both are a single problem without a tier, as they are not chosen by rating.
+    /// Otherwise, problems are first searched according to the theme of the date, if any, and then without it.
+    /// Tiers without an admissible problem are left out. Returns `None` if no problem was found at all.
+    ///
+    /// The choice only depends on the chat, the date, the configuration and the problems known from Codeforces,
+    /// so it can be previewed and reproduced.
+    pub async fn find_daily_problems(
         &self,
         cf_client: &codeforces::Client,
         chat_id: ChatId,
-    ) -> Result<codeforces::Problem> {
+        date: NaiveDate,
+    ) -> Result<Option<Vec<(Option<String>, codeforces::Problem)>>> {
+        if let Some(problem) = self
+            .problem_queue
+            .iter()
+            .find_map(|problem_id| self.problem_by_identifier.get(problem_id))
+        {
+            return Ok(Some(vec![(None, problem.clone())]));
+        }
+        match self.find_upsolving_problem(cf_client, date).await {
+            Ok(Some(problem)) => return Ok(Some(vec![(None, problem)])),
+            Ok(None) => {}
+            Err(report) => log::warn!(
+                "Could not search an upsolving problem for {:?}\n{}",
+                chat_id,
+                report
+            ),
+        }
+
+        let theme = self.theme_plan.theme_for(date);
+        if let Some(theme) = theme {
+            log::info!("The theme for {:?} on {} is {:?}", chat_id, date, theme);
+        }
         let mut rng: Xorshift128 = {
-            let unix_time_s = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .into_diagnostic()?
-                .as_secs();
-            let chat_hash = {
-                let mut hasher = DefaultHasher::new();
-                chat_id.hash(&mut hasher);
-                hasher.finish()
-            };
-            let states = [unix_time_s, chat_hash];
+            let states = stable_seed(&format!("{}:{}", chat_id.0, date.format("%Y-%m-%d")));
             SeedableRng::from_seed(&states[..])
         };
-        let known_problems = self.known_problems(cf_client).await;
+        // problems shown before are not drawn again, even if nobody tried them
+        let mut known_problems = self.known_problems(cf_client).await;
+        known_problems.extend(self.daily_problem_ids());
+        let problem_index = cf_client.problem_index().await?;
 
-        loop {
-            let tag_index = (rng.next_u64() as usize) % codeforces::TAGS.len();
-            let problems = cf_client
-                .get_problems_by_tag(std::iter::once(codeforces::TAGS[tag_index]))
-                .await?;
-            let mut problems: Vec<_> = problems
-                .into_iter()
-                .filter(|problem| {
-                    problem
-                        .rating
-                        .map_or(false, |rating| self.rating_range().contains(&rating))
-                        && !known_problems.contains(problem)
-                })
-                .collect();
-            log::debug!(
-                "For tag {} there are {} admissible problems",
-                codeforces::TAGS[tag_index],
-                problems.len()
-            );
+        let tiers: Vec<(Option<String>, RangeInclusive<u64>)> = if self.difficulty_tiers.is_empty()
+        {
+            vec![(None, self.target_rating_range(cf_client).await)]
+        } else {
+            self.difficulty_tiers
+                .iter()
+                .map(|tier| (Some(tier.name.clone()), tier.rating_range.clone()))
+                .collect()
+        };
+        let theme_tag_preferences = theme.map(|theme| TagPreferences {
+            included: if theme.tags.is_empty() {
+                self.tag_preferences.included.clone()
+            } else {
+                theme.tags.clone()
+            },
+            ..self.tag_preferences.clone()
+        });
+
+        let mut recent_tags = self.recent_tags();
+        let mut problems = Vec::with_capacity(tiers.len());
+        for (tier, target_rating_range) in tiers {
+            // difficulty tiers keep their own ranges
+            let theme_rating_range = theme
+                .filter(|_| tier.is_none())
+                .and_then(|theme| theme.rating_range.as_ref());
+            let themed_problem = theme_tag_preferences.as_ref().and_then(|tag_preferences| {
+                self.draw_problem(
+                    &mut rng,
+                    &problem_index,
+                    &known_problems,
+                    &recent_tags,
+                    tag_preferences,
+                    theme_rating_range.unwrap_or(&target_rating_range),
+                )
+            });
+            if theme.is_some() && themed_problem.is_none() {
+                log::info!(
+                    "Found no problem for the theme of tier {:?} in {:?}",
+                    tier,
+                    chat_id
+                );
+            }
+
+            let problem = themed_problem.or_else(|| {
+                self.draw_problem(
+                    &mut rng,
+                    &problem_index,
+                    &known_problems,
+                    &recent_tags,
+                    &self.tag_preferences,
+                    &target_rating_range,
+                )
+            });
+            match problem {
+                Some(problem) => {
+                    // overlapping tiers must not show the same problem twice,
+                    // and the other tiers should practice other topics
+                    known_problems.insert(problem.identifier()?);
+                    recent_tags.extend(problem.tags.iter().cloned());
+                    problems.push((tier, problem));
+                }
+                None => log::warn!("Found no problem for tier {:?} in {:?}", tier, chat_id),
+            }
+        }
+
+        Ok(if problems.is_empty() {
+            None
+        } else {
+            Some(problems)
+        })
+    }
+
+    /// Searches the first problem of a recent contest several registered users took part in,
+    /// which most of them have not solved and which was not a daily problem yet
+    pub async fn find_upsolving_problem(
+        &self,
+        cf_client: &codeforces::Client,
+        date: NaiveDate,
+    ) -> Result<Option<codeforces::Problem>> {
+        let Some(upsolving) = &self.upsolving else {
+            return Ok(None);
+        };
+        let max_age_s = i64::try_from(upsolving.max_age_days)
+            .ok()
+            .and_then(|max_age_days| max_age_days.checked_mul(24 * 60 * 60))
+            .ok_or_else(|| miette!("{} days are too many to upsolve", upsolving.max_age_days))?;
+        let min_time = date
+            .and_hms_opt(0, 0, 0)
+            .ok_or_else(|| miette!("Midnight does not exist on {}", date))?
+            .timestamp()
+            .saturating_sub(max_age_s);
 
-            if !problems.is_empty() {
-                return Ok(problems.swap_remove((rng.next_u64() as usize) % problems.len()));
+        let mut participants: HashMap<u64, HashSet<&codeforces::Handle>> = HashMap::new();
+        let mut last_participation: HashMap<u64, i64> = HashMap::new();
+        let mut solvers: HashMap<codeforces::ProblemIdentifier, HashSet<&codeforces::Handle>> =
+            HashMap::new();
+        let handles: BTreeSet<_> = self.registered_users().map(|(_, handle)| handle).collect();
+        for handle in handles {
+            let summary = match cf_client.submission_summary(handle).await {
+                Ok(summary) => summary,
+                Err(err) => {
+                    log::warn!("Error getting submissions for {}\n{}", handle.as_str(), err);
+                    continue;
+                }
+            };
+            for (problem_id, verdict_category) in summary.verdict_per_problem() {
+                if *verdict_category == codeforces::VerdictCategory::Correct {
+                    solvers
+                        .entry(problem_id.clone())
+                        .or_default()
+                        .insert(handle);
+                }
+            }
+            for (contest_id, participation_time) in summary.participations() {
+                if *participation_time >= min_time {
+                    participants.entry(*contest_id).or_default().insert(handle);
+                    let last = last_participation.entry(*contest_id).or_default();
+                    *last = Ord::max(*last, *participation_time);
+                }
+            }
+        }
+
+        // most recent contests first
+        let mut contests: Vec<_> = participants
+            .into_iter()
+            .filter(|(_, handles)| handles.len() >= upsolving.min_participants)
+            .collect();
+        contests.sort_unstable_by_key(|(contest_id, _)| {
+            (Reverse(last_participation[contest_id]), *contest_id)
+        });
+
+        let daily_problem_ids = self.daily_problem_ids();
+        for (contest_id, handles) in contests {
+            for problem in cf_client.get_contest_problems(contest_id).await? {
+                let problem_id = problem.identifier()?;
+                if daily_problem_ids.contains(&problem_id) {
+                    continue;
+                }
+                let solved_count = solvers
+                    .get(&problem_id)
+                    .map_or(0, |solvers| solvers.intersection(&handles).count());
+                if 2 * solved_count < handles.len() {
+                    log::debug!(
+                        "{} of {} participants solved {:?}",
+                        solved_count,
+                        handles.len(),
+                        problem_id
+                    );
+                    return Ok(Some(problem));
+                }
             }
-            log::warn!("Tag {} has no viable problems", codeforces::TAGS[tag_index],);
         }
+
+        Ok(None)
     }
 
+    /// Draws a problem that is not known, within the (possibly widened) target rating range.
+    ///
+    /// Returns `None` if every tag has been tried for every allowed widening of the rating range.
+    fn draw_problem(
+        &self,
+        rng: &mut impl Rng,
+        problem_index: &codeforces::ProblemIndex,
+        known_problems: &HashSet<codeforces::ProblemIdentifier>,
+        recent_tags: &HashSet<String>,
+        tag_preferences: &TagPreferences,
+        target_rating_range: &RangeInclusive<u64>,
+    ) -> Option<codeforces::Problem> {
+        for widening_steps in 0..=self.rating_widening.max_steps {
+            let rating_range = self
+                .rating_widening
+                .widen(target_rating_range, widening_steps);
+
+            // try every drawable tag once, in random order
+            for tag in tag_preferences.draw_order(rng, recent_tags) {
+                let filter = codeforces::ProblemFilter {
+                    rating: Some(rating_range.clone()),
+                    tags: vec![String::from(tag)],
+                    excluded_tags: tag_preferences.excluded.iter().cloned().collect(),
+                    contest_kinds: self.contest_filter.kinds.clone(),
+                    min_contest_year: self.contest_filter.min_year,
+                    solved_count: self.popularity.solved_count(),
+                    ..Default::default()
+                };
+                let problems = least_recent(
+                    problem_index
+                        .query(&filter)
+                        .filter(|indexed_problem| {
+                            indexed_problem
+                                .problem
+                                .identifier()
+                                .is_ok_and(|problem_id| !known_problems.contains(&problem_id))
+                        })
+                        .collect(),
+                    recent_tags,
+                );
+                log::debug!(
+                    "For tag {} and rating range {:?} there are {} admissible problems",
+                    tag,
+                    rating_range,
+                    problems.len()
+                );
+
+                if !problems.is_empty() {
+                    let candidates = self.popularity.candidates(problems);
+                    return Some(
+                        candidates[(rng.next_u64() as usize) % candidates.len()]
+                            .problem
+                            .clone(),
+                    );
+                }
+            }
+            log::warn!("Rating range {:?} has no viable problems", rating_range);
+            if self.rating_widening.is_exhausted(&rating_range) {
+                break;
+            }
+        }
+
+        None
+    }
+
+    pub async fn no_daily_problem_text(&self, cf_client: &codeforces::Client) -> String {
+        let contest_commands = match (
+            self.contest_filter.kinds.is_empty(),
+            self.contest_filter.min_year,
+        ) {
+            (true, None) => "",
+            (false, None) => ", /setdivisions",
+            (true, Some(_)) => ", /setminyear",
+            (false, Some(_)) => ", /setdivisions, /setminyear",
+        };
+        if !self.difficulty_tiers.is_empty() {
+            return format!(
+                "Could not find a problem {} for any difficulty tier (widened by up to {} per bound). Use /settiers, /setwidening{}, /includetags or /excludetags to consider other problems",
+                self.known_problem_policy.unknown_text(),
+                self.rating_widening
+                    .step
+                    .saturating_mul(self.rating_widening.max_steps),
+                contest_commands,
+            );
+        }
+
+        let rating_range = self.target_rating_range(cf_client).await;
+        let widened_range = self
+            .rating_widening
+            .widen(&rating_range, self.rating_widening.max_steps);
+        format!(
+            "Could not find a problem with a rating between {} and {} (widened up to {} and {}) {}. Use /setrange, /setwidening{}, /includetags or /excludetags to consider other problems",
+            rating_range.start(),
+            rating_range.end(),
+            widened_range.start(),
+            widened_range.end(),
+            self.known_problem_policy.unknown_text(),
+            contest_commands,
+        )
+    }
+
+    pub fn registrations_text(&self) -> String {
+        let mut registrations: Vec<_> = self.registered_users().collect();
+        if registrations.is_empty() {
+            return String::from("There are no registered users");
+        }
+        registrations.sort_unstable();
+
+        let mut result = String::from("Current Registrations:\n");
+        for (display_name, codeforces_handle) in registrations {
+            result.push_str("Name: ");
+            result.push_str(display_name);
+            result.push('\t');
+            result.push_str("Handle: ");
+            result.push_str(codeforces_handle.as_str());
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Renders a daily message showing the given problems, with one status column per problem
     pub fn message_text_for_problem(
         &self,
-        problem: &codeforces::Problem,
-        status: &HashMap<codeforces::Handle, codeforces::VerdictCategory>,
+        daily_problems: &[DailyProblem],
+        status: &HashMap<
+            codeforces::ProblemIdentifier,
+            HashMap<codeforces::Handle, codeforces::VerdictCategory>,
+        >,
     ) -> Result<String> {
         let status_str = |verdict_category_opt| match verdict_category_opt {
             Some(codeforces::VerdictCategory::Correct) => "🟩️",
@@ -114,26 +1394,58 @@ impl ChannelState {
             Some(codeforces::VerdictCategory::Incorrect) => "🟥️",
             None => "⬜",
         };
+        let url = |daily_problem: &DailyProblem| {
+            self.problem_by_identifier
+                .get(&daily_problem.problem_id)
+                .ok_or_else(|| miette!("For a daily Problem Identifier there is no known Problem"))?
+                .url()
+        };
 
-        let mut message = format!("Today's problem is: {}", problem.url()?);
+        let mut message = match daily_problems {
+            [daily_problem @ DailyProblem { tier: None, .. }] => {
+                format!("Today's problem is: {}", url(daily_problem)?)
+            }
+            _ => {
+                let mut message = String::from("Today's problems are:");
+                for daily_problem in daily_problems {
+                    message.push('\n');
+                    message.push_str(daily_problem.tier.as_deref().unwrap_or("-"));
+                    message.push_str(": ");
+                    message.push_str(&url(daily_problem)?);
+                }
+                message
+            }
+        };
 
-        if !self.registered_users.is_empty() {
+        let mut data: Vec<_> = self
+            .registered_users()
+            .map(|(display_name, handle)| {
+                let verdicts: Vec<_> = daily_problems
+                    .iter()
+                    .map(|daily_problem| {
+                        status
+                            .get(&daily_problem.problem_id)
+                            .and_then(|problem_status| problem_status.get(handle))
+                            .copied()
+                    })
+                    .collect();
+                (verdicts, display_name)
+            })
+            .collect();
+        if !data.is_empty() {
             message.push_str("\n\n");
 
-            let mut data: Vec<_> = self
-                .registered_users
-                .iter()
-                .map(|(display_name, handle)| (status.get(handle).copied(), display_name))
-                .collect();
-            data.sort_unstable_by(|(verdict1, name1), (verdict2, name2)| {
-                match verdict1.cmp(verdict2) {
+            data.sort_unstable_by(|(verdicts1, name1), (verdicts2, name2)| {
+                match verdicts1.cmp(verdicts2) {
                     Ordering::Equal => name1.cmp(name2),
                     order => order.reverse(),
                 }
             });
 
-            for (verdict_category_opt, display_name) in data {
-                message.push_str(status_str(verdict_category_opt));
+            for (verdicts, display_name) in data {
+                for verdict_category_opt in verdicts {
+                    message.push_str(status_str(verdict_category_opt));
+                }
                 message.push(' ');
                 message.push_str(display_name);
                 message.push('\n');
@@ -143,3 +1455,184 @@ impl ChannelState {
         Ok(message.trim_end().into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded_rng() -> Xorshift128 {
+        SeedableRng::from_seed(&stable_seed("seed")[..])
+    }
+
+    #[test]
+    fn stable_hash_is_fnv_1a() {
+        assert_eq!(stable_hash(""), 0xcbf29ce484222325);
+        assert_eq!(stable_hash("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(stable_hash("foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn stable_seed_is_fixed() {
+        assert_eq!(
+            stable_seed("-100:2024-01-01"),
+            stable_seed("-100:2024-01-01")
+        );
+        assert_ne!(
+            stable_seed("-100:2024-01-01"),
+            stable_seed("-100:2024-01-02")
+        );
+        let [first, second] = stable_seed("");
+        assert_eq!(first, 0xcbf29ce484222325);
+        assert_ne!(second, 0);
+    }
+
+    #[test]
+    fn draw_order_only_contains_drawable_tags() {
+        let tag_preferences = TagPreferences {
+            included: BTreeSet::from([String::from("dp"), String::from("graphs")]),
+            excluded: BTreeSet::from([String::from("graphs")]),
+            weights: BTreeMap::from([(String::from("math"), 5)]),
+        };
+        let order = tag_preferences.draw_order(&mut seeded_rng(), &HashSet::new());
+        assert_eq!(order, vec!["dp"]);
+    }
+
+    #[test]
+    fn draw_order_puts_recent_tags_last() {
+        let tag_preferences = TagPreferences::default();
+        let recent_tags = HashSet::from([String::from("dp"), String::from("greedy")]);
+        let order = tag_preferences.draw_order(&mut seeded_rng(), &recent_tags);
+        assert_eq!(order.len(), codeforces::TAGS.len());
+        let (_, recent) = order.split_at(order.len() - recent_tags.len());
+        assert!(recent.iter().all(|tag| recent_tags.contains(*tag)));
+    }
+
+    #[test]
+    fn draw_order_does_not_overflow() {
+        let tag_preferences = TagPreferences {
+            weights: BTreeMap::from([
+                (String::from("dp"), u64::MAX),
+                (String::from("graphs"), u64::MAX),
+            ]),
+            ..Default::default()
+        };
+        let order = tag_preferences.draw_order(&mut seeded_rng(), &HashSet::new());
+        assert_eq!(order.len(), codeforces::TAGS.len());
+    }
+
+    fn indexed_problem(index: &str, tags: &[&str]) -> codeforces::IndexedProblem {
+        codeforces::IndexedProblem {
+            problem: Problem {
+                index: String::from(index),
+                name: String::from(index),
+                tags: tags.iter().map(|tag| String::from(*tag)).collect(),
+                rating: Some(1500),
+                contest_id: Some(1000),
+                problemset_name: None,
+            },
+            solved_count: 0,
+            contest_kinds: BTreeSet::new(),
+            contest_year: None,
+        }
+    }
+
+    #[test]
+    fn candidates_break_ties_by_identifier() {
+        let mut problems: Vec<_> = ["D", "A", "C", "B", "E", "F", "G", "H"]
+            .into_iter()
+            .map(|index| indexed_problem(index, &[]))
+            .collect();
+        problems[0].solved_count = 10;
+        let preferences = |preferred| PopularityPreferences {
+            preferred,
+            ..Default::default()
+        };
+
+        let classic = preferences(Popularity::Classic).candidates(problems.iter().collect());
+        assert_eq!(classic, vec![&problems[0], &problems[1]]);
+        let obscure = preferences(Popularity::Obscure).candidates(problems.iter().collect());
+        assert_eq!(obscure, vec![&problems[1], &problems[3]]);
+    }
+
+    #[test]
+    fn least_recent_avoids_recent_secondary_tags() {
+        let problems = [
+            indexed_problem("A", &["dp", "graphs"]),
+            indexed_problem("B", &["dp", "math"]),
+            indexed_problem("C", &["dp", "graphs", "math"]),
+            indexed_problem("D", &["dp"]),
+        ];
+        let recent_tags = HashSet::from([String::from("graphs"), String::from("math")]);
+        let candidates = least_recent(problems.iter().collect(), &recent_tags);
+        assert_eq!(candidates, vec![&problems[3]]);
+
+        // if every problem has a recent tag, those with the fewest remain
+        let candidates = least_recent(problems[..3].iter().collect(), &recent_tags);
+        assert_eq!(candidates, vec![&problems[0], &problems[1]]);
+    }
+
+    #[test]
+    fn review_mode_is_stored_by_name() {
+        for review_mode in ReviewMode::ALL {
+            let json = serde_json::to_value(review_mode).unwrap();
+            assert_eq!(json, serde_json::json!(review_mode.name()));
+            assert_eq!(
+                serde_json::from_value::<ReviewMode>(json).unwrap(),
+                review_mode
+            );
+        }
+        assert_eq!(
+            serde_json::from_str::<ReviewMode>("\"direct\"").unwrap(),
+            ReviewMode::Direct
+        );
+    }
+
+    #[test]
+    fn reroll_vote_expiry_does_not_overflow() {
+        let vote = RerollVote {
+            message_id: MessageId(2),
+            daily_message_id: MessageId(1),
+            voters: BTreeSet::new(),
+            started_at: 1000,
+        };
+        let settings = |minutes| RerollSettings { quorum: 3, minutes };
+        assert!(!vote.is_expired(&settings(1), 1060));
+        assert!(vote.is_expired(&settings(1), 1061));
+        assert!(!vote.is_expired(&settings(u64::MAX), i64::MAX));
+        assert!(!vote.is_expired(&settings(u64::MAX / 60 + 1), i64::MAX));
+    }
+
+    #[test]
+    fn widen_moves_both_bounds() {
+        let widening = RatingWidening {
+            step: 100,
+            max_steps: 2,
+        };
+        assert_eq!(widening.widen(&(1200..=1400), 0), 1200..=1400);
+        assert_eq!(widening.widen(&(1200..=1400), 2), 1000..=1600);
+        assert_eq!(widening.widen(&(100..=400), 2), 0..=600);
+    }
+
+    #[test]
+    fn widen_saturates() {
+        let widening = RatingWidening {
+            step: u64::MAX,
+            max_steps: u64::MAX,
+        };
+        assert_eq!(widening.widen(&(1200..=1400), u64::MAX), 0..=u64::MAX);
+        assert!(widening.is_exhausted(&widening.widen(&(1200..=1400), 1)));
+    }
+
+    #[test]
+    fn widening_is_exhausted_once_all_ratings_are_covered() {
+        let widening = RatingWidening::default();
+        assert!(!widening.is_exhausted(&(800..=3500)));
+        assert!(!widening.is_exhausted(&(0..=3400)));
+        assert!(widening.is_exhausted(&(0..=3500)));
+        assert!(RatingWidening {
+            step: 0,
+            max_steps: 5
+        }
+        .is_exhausted(&(1200..=1400)));
+    }
+}
@@ -7,6 +7,7 @@ use tokio::sync::oneshot;
 
 use crate::codeforces;
 use crate::telegram_bot::dispatcher::MyStorage;
+use crate::telegram_bot::channel_state::{DailyMessage, DailyProblem, ReviewMode};
 use crate::telegram_bot::ChannelState;
 use TelegramControlCommand::*;
 
@@ -16,9 +17,21 @@ pub enum TelegramControlCommand {
         chat_id: ChatId,
         return_send: oneshot::Sender<ChannelState>,
     },
+    /// Sends a daily message with the given problems, each with the name of its tier if the chat has tiers
     SetAndNotifyDailyProblem {
         chat_id: ChatId,
-        problem: codeforces::Problem,
+        problems: Vec<(Option<String>, codeforces::Problem)>,
+        /// Whether a due review is sent along, which scheduled daily messages do but rerolls do not
+        with_review: bool,
+    },
+    SendMessage {
+        chat_id: ChatId,
+        text: String,
+    },
+    /// Sends the most overdue review of a failed daily problem, if there is one,
+    /// for days without a daily message, which otherwise sends the review itself
+    SendReview {
+        chat_id: ChatId,
     },
     UpdateSolvingStatus {
         chat_id: ChatId,
@@ -57,7 +70,8 @@ pub async fn handle(
         }
         SetAndNotifyDailyProblem {
             chat_id,
-            problem: new_problem,
+            problems: new_problems,
+            with_review,
         } => {
             let mut state: ChannelState = storage
                 .clone()
@@ -65,33 +79,56 @@ pub async fn handle(
                 .await
                 .into_diagnostic()?
                 .unwrap_or_default();
+            state.migrate_current_daily_message()?;
+
+            // a vote on replacing the archived message is over
+            state.reroll_vote = None;
 
-            // archive problem
-            if let (Some(current_problem), Some(current_message)) =
-                (&state.current_daily_problem, &state.current_daily_message)
-            {
+            // archive message
+            if let Some(current_message) = state.current_daily_message.take() {
+                state.archived_daily_messages.push(current_message);
+            }
+
+            // update problems
+            state.record_daily_tags(
+                new_problems
+                    .iter()
+                    .flat_map(|(_, new_problem)| new_problem.tags.iter().cloned())
+                    .collect(),
+            );
+            let mut daily_problems = Vec::with_capacity(new_problems.len());
+            for (tier, new_problem) in new_problems {
+                let problem_id = new_problem.identifier()?;
                 state
-                    .archived_daily_messages
-                    .entry(current_problem.identifier()?)
-                    .or_insert_with(Default::default)
-                    .push(current_message.clone());
+                    .problem_by_identifier
+                    .insert(problem_id.clone(), new_problem);
+                daily_problems.push(DailyProblem { tier, problem_id });
             }
+            state.problem_queue.retain(|problem_id| {
+                !daily_problems
+                    .iter()
+                    .any(|daily_problem| daily_problem.problem_id == *problem_id)
+            });
 
             // update message
             let new_message = bot
                 .send_message(
                     chat_id,
-                    state.message_text_for_problem(&new_problem, &HashMap::new())?,
+                    state.message_text_for_problem(&daily_problems, &HashMap::new())?,
                 )
                 .await
                 .into_diagnostic()?;
-            state.current_daily_message = Some(new_message);
+            state.current_daily_message = Some(DailyMessage {
+                problems: daily_problems,
+                message: new_message,
+            });
 
-            // update problem
-            state
-                .problem_by_identifier
-                .insert(new_problem.identifier()?, new_problem.clone());
-            state.current_daily_problem = Some(new_problem);
+            // reviewing in the same update keeps it from overwriting the new daily message
+            if with_review && state.review_mode() != ReviewMode::Off {
+                if let Err(report) = send_review(&bot, &mut state, chat_id).await {
+                    log::error!("Could not send review to {:?}\n{}", chat_id, report);
+                }
+            }
 
             // save to storage
             storage
@@ -100,6 +137,25 @@ pub async fn handle(
                 .into_diagnostic()?;
             Ok(())
         }
+        SendMessage { chat_id, text } => {
+            bot.send_message(chat_id, text).await.into_diagnostic()?;
+            Ok(())
+        }
+        SendReview { chat_id } => {
+            let mut state: ChannelState = storage
+                .clone()
+                .get_dialogue(chat_id)
+                .await
+                .into_diagnostic()?
+                .unwrap_or_default();
+            send_review(&bot, &mut state, chat_id).await?;
+
+            storage
+                .update_dialogue(chat_id, state)
+                .await
+                .into_diagnostic()?;
+            Ok(())
+        }
         UpdateSolvingStatus { chat_id, status } => {
             log::debug!(
                 "Current solving status for chat {:?} is {:?}",
@@ -113,41 +169,22 @@ pub async fn handle(
                 .into_diagnostic()?
                 .unwrap_or_default();
 
-            let default_map = HashMap::new();
+            state.migrate_current_daily_message()?;
             let saved_state = state.clone();
             let mut changed = false;
 
             // update current daily message
-            if let (Some(daily_problem), Some(daily_message)) = (
-                &state.current_daily_problem,
-                &mut state.current_daily_message,
-            ) {
-                changed |= update_message(
-                    &saved_state,
-                    daily_problem,
-                    status.get(&daily_problem.identifier()?).unwrap_or(&default_map),
-                    &bot,
-                    daily_message,
-                )
-                .await?;
+            if let Some(daily_message) = &mut state.current_daily_message {
+                changed |= update_message(&saved_state, &status, &bot, daily_message).await?;
             }
 
             // update archived messages
-            for (problem_id, messages) in state.archived_daily_messages.iter_mut() {
-                for message in messages.iter_mut() {
-                    changed |= update_message(
-                        &saved_state,
-                        state.problem_by_identifier.get(problem_id).ok_or_else(|| {
-                            miette!("For an archived Problem Identifiere there is no known Problem")
-                        })?,
-                        status.get(problem_id).unwrap_or(&default_map),
-                        &bot,
-                        message,
-                    )
-                    .await?;
-                }
+            for daily_message in state.archived_daily_messages.iter_mut() {
+                changed |= update_message(&saved_state, &status, &bot, daily_message).await?;
             }
 
+            changed |= state.update_review_items(&status);
+
             if changed {
                 storage
                     .update_dialogue(chat_id, state)
@@ -159,19 +196,50 @@ pub async fn handle(
     }
 }
 
+/// Sends the most overdue review of a failed daily problem, if there is one, and schedules its next repetition
+async fn send_review(bot: &Bot, state: &mut ChannelState, chat_id: ChatId) -> Result<()> {
+    let Some((problem_id, review_item)) = state.take_due_review() else {
+        return Ok(());
+    };
+    let text = state.review_text(&problem_id, &review_item)?;
+
+    let recipients: Vec<UserId> = state
+        .registered_users
+        .iter()
+        .filter(|(_, user)| review_item.failed_handles.contains(&user.handle))
+        .map(|(user_id, _)| *user_id)
+        .collect();
+    // users registered by name only can not be sent direct messages
+    let all_direct = recipients.len() == review_item.failed_handles.len();
+    if state.review_mode() == ReviewMode::Direct && all_direct {
+        for user_id in recipients {
+            if let Err(err) = bot.send_message(user_id, text.clone()).await {
+                // users have to start a chat with the bot before it can message them
+                log::warn!("Could not send review to {:?}\n{}", user_id, err);
+            }
+        }
+    } else {
+        bot.send_message(chat_id, text).await.into_diagnostic()?;
+    }
+    Ok(())
+}
+
 async fn update_message(
     channel: &ChannelState,
-    problem: &codeforces::Problem,
-    status: &HashMap<codeforces::Handle, codeforces::VerdictCategory>,
+    status: &HashMap<
+        codeforces::ProblemIdentifier,
+        HashMap<codeforces::Handle, codeforces::VerdictCategory>,
+    >,
     bot: &Bot,
-    message: &mut Message,
+    daily_message: &mut DailyMessage,
 ) -> Result<bool> {
+    let DailyMessage { problems, message } = daily_message;
     log::trace!(
-        "update_message:\n\tproblem({:?})\n\tstatus({:?})",
-        problem.identifier()?,
+        "update_message:\n\tproblems({:?})\n\tstatus({:?})",
+        problems,
         status
     );
-    let new_text = channel.message_text_for_problem(problem, status)?;
+    let new_text = channel.message_text_for_problem(problems, status)?;
 
     if new_text
         == message
@@ -179,10 +247,10 @@ async fn update_message(
             .ok_or_else(|| miette!("Tried updating message without text"))?
     {
         log::trace!(
-            "Message {:?} in {:?} for problem {:?} does not need to be changed.\nOld: {:?}\nNew:{:?}",
+            "Message {:?} in {:?} for problems {:?} does not need to be changed.\nOld: {:?}\nNew:{:?}",
             message.id,
             message.chat.id,
-            problem,
+            problems,
             message.text().unwrap(),
             new_text,
         );
@@ -1,15 +1,33 @@
 use crate::options::Options;
 use crate::scheduler::SchedulerControlCommand;
-use crate::telegram_bot::{channel_state::ChannelState, TelegramControlCommand};
+use crate::telegram_bot::channel_state::{
+    AutoRatingRange, ChannelState, DifficultyTier, KnownProblemPolicy, MessageSchedule,
+    PendingVerification, Popularity, RatingWidening, RerollSettings, RerollVote, ReviewMode,
+    TagRotation, ThemeRule, Upsolving, MAX_TAG_WEIGHT,
+};
+use crate::telegram_bot::{problem_list, TelegramControlCommand};
 use crate::{codeforces, util};
+use chrono::{NaiveDate, NaiveTime, Timelike, Weekday};
 use miette::{miette, IntoDiagnostic, Result};
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use teloxide::dispatching::{dialogue, ShutdownToken, UpdateHandler};
+use teloxide::net::Download;
 use teloxide::prelude::*;
-use teloxide::utils::command::BotCommands;
+use teloxide::types::{Chat, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, User};
+use teloxide::utils::command::{BotCommands, ParseError};
 use teloxide::{dptree, Bot};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
+use xorshift::{Rng, SeedableRng, Xorshift128};
+
+/// Number of most recent submissions searched for the verifying compilation error
+const VERIFICATION_SUBMISSION_COUNT: u64 = 20;
+/// Data of the button voting for a reroll
+const REROLL_CALLBACK_DATA: &str = "reroll";
+/// Days within which contests are considered for upsolving, if not given
+const DEFAULT_UPSOLVING_DAYS: u64 = 14;
 
 #[derive(BotCommands, Clone, Debug)]
 #[command(
@@ -22,175 +40,2043 @@ enum ChannelCommand {
     Help,
     #[command(description = "(Re)starts the bot in this channel.")]
     Start,
-    #[command(description = "Forces the bot to send a new daily message.")]
-    NewProblem,
-    #[command(description = "Register a user.\n\tUsage: /register <display-name> <cf-handle>")]
-    Register {
-        display_name: String,
-        codeforces_handle: String,
-    },
+    #[command(description = "Pauses the daily messages in this channel.")]
+    Pause,
+    #[command(description = "Resumes paused daily messages in this channel.")]
+    Resume,
+    #[command(description = "Stops the bot in this channel.")]
+    Stop,
+    #[command(description = "Start a vote on replacing today's problem.")]
+    Reroll,
+    #[command(
+        rename = "setreroll",
+        description = "Set how many votes within how many minutes replace today's problem.\n\tUsage: /setreroll <quorum> <minutes>"
+    )]
+    SetReroll { quorum: usize, minutes: u64 },
+    #[command(description = "Shows which problems would be sent tomorrow, without sending them.")]
+    Preview,
+    #[command(
+        parse_with = "default",
+        description = "Register yourself.\n\tUsage: /me <cf-handle>"
+    )]
+    Me { codeforces_handle: String },
+    #[command(
+        description = "Verify your Codeforces handle after submitting the requested compilation error."
+    )]
+    Verify,
+    #[command(
+        parse_with = "default",
+        description = "Ask the author of the replied to message to verify and register the handle.\n\tUsage: /register <cf-handle>"
+    )]
+    Register { codeforces_handle: String },
+    #[command(
+        parse_with = "default",
+        description = "Unregister yourself, the author of the replied to message or the given user.\n\tUsage: /unregister [display-name]"
+    )]
+    Unregister { display_name: String },
+    #[command(description = "List the registered users.")]
+    Users,
+    #[command(
+        description = "Allow the author of the replied to message to change the configuration."
+    )]
+    Allow,
+    #[command(
+        description = "Disallow the author of the replied to message to change the configuration."
+    )]
+    Disallow,
     #[command(
         rename = "setrange",
         description = "Set the considered rating range.\n\tUsage: /setrange <lower-bound> <upper-bound>"
     )]
     SetRatingRange { lower_bound: u64, upper_bound: u64 },
+    #[command(
+        rename = "autorange",
+        description = "Derive the rating range from the median rating of the registered users.\n\tUsage: /autorange <offset> <width>"
+    )]
+    SetAutoRatingRange { offset: i64, width: u64 },
+    #[command(
+        rename = "setwidening",
+        description = "Set how the rating range is widened if it has no suitable problem.\n\tUsage: /setwidening <step> <max-steps>"
+    )]
+    SetRatingWidening { step: u64, max_steps: u64 },
+    #[command(
+        parse_with = "default",
+        rename = "settiers",
+        description = "Draw one problem per named difficulty tier, or one from the rating range if none are given.\n\tUsage: /settiers [name lower-upper, ...]"
+    )]
+    SetDifficultyTiers { tiers: String },
+    #[command(
+        parse_with = "default",
+        rename = "includetags",
+        description = "Only draw the given tags, or all tags if none are given.\n\tUsage: /includetags [tag, ...]"
+    )]
+    IncludeTags { tags: String },
+    #[command(
+        parse_with = "default",
+        rename = "excludetags",
+        description = "Never choose problems with the given tags.\n\tUsage: /excludetags [tag, ...]"
+    )]
+    ExcludeTags { tags: String },
+    #[command(
+        parse_with = "default",
+        rename = "tagweights",
+        description = "Set how often tags are drawn relative to each other.\n\tUsage: /tagweights [tag=weight, ...]"
+    )]
+    TagWeights { weights: String },
+    #[command(
+        parse_with = parse_single_number,
+        rename = "setrotation",
+        description = "Avoid the tags of the daily problems of the last days.\n\tUsage: /setrotation <days>"
+    )]
+    SetTagRotation { days: u64 },
+    #[command(description = "Show the tag preferences and which problems are chosen.")]
+    Tags,
+    #[command(
+        parse_with = "default",
+        rename = "setdivisions",
+        description = "Only choose problems of contests of the given kinds, or of all contests if none are given.\n\tUsage: /setdivisions [div1|div2|div3|div4|educational|global, ...]"
+    )]
+    SetContestKinds { kinds: String },
+    #[command(
+        parse_with = "default",
+        rename = "setminyear",
+        description = "Only choose problems of contests held in or after the given year, or of any year if none is given.\n\tUsage: /setminyear [year]"
+    )]
+    SetMinContestYear { year: String },
+    #[command(
+        parse_with = "default",
+        rename = "setsolved",
+        description = "Only choose problems solved by at least min and at most max people, or by any number if no bounds are given.\n\tUsage: /setsolved [min]-[max]"
+    )]
+    SetSolvedCount { bounds: String },
+    #[command(
+        parse_with = "default",
+        description = "Prefer problems solved by many (classic) or by few (obscure) people.\n\tUsage: /popularity <any|classic|obscure>"
+    )]
+    Popularity { popularity: String },
+    #[command(
+        parse_with = "default",
+        description = "Send failed daily problems again after growing intervals, in this chat or as direct messages.\n\tUsage: /review <off|chat|dm>"
+    )]
+    Review { mode: String },
+    #[command(
+        parse_with = "default",
+        description = "Take the daily problem from contests at least the given number of registered users took part in within the given days, instead of one per tier, or stop doing so.\n\tUsage: /upsolve <off|min-participants> [max-age-days]"
+    )]
+    Upsolve { settings: String },
+    #[command(
+        parse_with = "default",
+        description = "Set which problems are never chosen: those attempted by anyone, solved by anyone, or solved by at least k registered users.\n\tUsage: /known <attempted|solved|solved k>"
+    )]
+    Known { policy: String },
+    #[command(
+        parse_with = "default",
+        rename = "settheme",
+        description = "Plan the tags and rating range of the daily problem on a weekday or date, or remove the theme if neither is given.\n\tUsage: /settheme <weekday|yyyy-mm-dd> [tag, ...] [lower-upper]"
+    )]
+    SetTheme { theme: String },
+    #[command(description = "Show the planned themes.")]
+    Themes,
+    #[command(description = "Show the queued problems.")]
+    Queue,
+    #[command(
+        parse_with = "default",
+        description = "Queue problems to be sent before any are drawn, given by Codeforces URLs or a JSON or CSV file sent with this caption.\n\tUsage: /enqueue <url> ..."
+    )]
+    Enqueue { problems: String },
+    #[command(
+        rename = "movequeued",
+        description = "Move a queued problem to another position.\n\tUsage: /movequeued <from> <to>"
+    )]
+    MoveQueued { from: usize, to: usize },
+    #[command(
+        parse_with = "default",
+        description = "Remove the queued problem at the given position, or all if none is given.\n\tUsage: /dequeue [position]"
+    )]
+    Dequeue { position: String },
+    #[command(
+        rename = "settime",
+        description = "Set the time of the daily message.\n\tUsage: /settime <hh:mm> <timezone>"
+    )]
+    SetTime { time: String, timezone: String },
+}
+
+#[cfg(not(feature = "persistent"))]
+pub type MyStorage = teloxide::dispatching::dialogue::InMemStorage<ChannelState>;
+#[cfg(feature = "persistent")]
+pub type MyStorage = teloxide::dispatching::dialogue::RedisStorage<
+    teloxide::dispatching::dialogue::serializer::Json,
+>;
+
+pub type MyDialogue = Dialogue<ChannelState, MyStorage>;
+
+async fn start(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    sched_send: mpsc::UnboundedSender<SchedulerControlCommand>,
+    msg: Message,
+) -> Result<()> {
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    state.paused = false;
+    dialogue.update(state).await.into_diagnostic()?;
+
+    sched_send
+        .send(SchedulerControlCommand::StartDailyMessages {
+            chat_id: msg.chat.id,
+        })
+        .into_diagnostic()?;
+    bot.send_message(msg.chat.id, "A daily problem will be prepared for you🍴")
+        .await
+        .into_diagnostic()?;
+    Ok(())
+}
+
+async fn pause(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    sched_send: mpsc::UnboundedSender<SchedulerControlCommand>,
+    msg: Message,
+) -> Result<()> {
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    if state.paused {
+        bot.send_message(msg.chat.id, "Daily problems are already paused")
+            .await
+            .into_diagnostic()?;
+        return Ok(());
+    }
+    state.paused = true;
+    dialogue.update(state).await.into_diagnostic()?;
+
+    sched_send
+        .send(SchedulerControlCommand::PauseDailyMessages {
+            chat_id: msg.chat.id,
+        })
+        .into_diagnostic()?;
+    bot.send_message(
+        msg.chat.id,
+        "Daily problems are paused. Use /resume to continue",
+    )
+    .await
+    .into_diagnostic()?;
+    Ok(())
+}
+
+async fn resume(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    sched_send: mpsc::UnboundedSender<SchedulerControlCommand>,
+    msg: Message,
+) -> Result<()> {
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    if !state.paused {
+        bot.send_message(msg.chat.id, "Daily problems are not paused")
+            .await
+            .into_diagnostic()?;
+        return Ok(());
+    }
+    state.paused = false;
+    dialogue.update(state).await.into_diagnostic()?;
+
+    // the scheduler replies, as only it knows whether daily messages were started
+    sched_send
+        .send(SchedulerControlCommand::ResumeDailyMessages {
+            chat_id: msg.chat.id,
+        })
+        .into_diagnostic()
+}
+
+async fn stop(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    sched_send: mpsc::UnboundedSender<SchedulerControlCommand>,
+    msg: Message,
+) -> Result<()> {
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    state.paused = false;
+    dialogue.update(state).await.into_diagnostic()?;
+
+    sched_send
+        .send(SchedulerControlCommand::StopDailyMessages {
+            chat_id: msg.chat.id,
+        })
+        .into_diagnostic()?;
+    bot.send_message(
+        msg.chat.id,
+        "Daily problems are stopped. Use /start to start them again",
+    )
+    .await
+    .into_diagnostic()?;
+    Ok(())
+}
+
+/// Draws new problems for today, which replace the current daily problems
+async fn replace_daily_problems(
+    bot: &Bot,
+    telegram_send: &mpsc::UnboundedSender<TelegramControlCommand>,
+    cf_client: &codeforces::Client,
+    chat_id: ChatId,
+) -> Result<()> {
+    let channel_state = util::get_channel_state(chat_id, telegram_send).await?;
+    match channel_state
+        .find_daily_problems(cf_client, chat_id, channel_state.today())
+        .await?
+    {
+        Some(problems) => telegram_send
+            .send(TelegramControlCommand::SetAndNotifyDailyProblem {
+                chat_id,
+                problems,
+                with_review: false,
+            })
+            .into_diagnostic(),
+        None => bot
+            .send_message(
+                chat_id,
+                channel_state.no_daily_problem_text(cf_client).await,
+            )
+            .await
+            .into_diagnostic()
+            .map(|_| ()),
+    }
+}
+
+fn reroll_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        "Reroll",
+        REROLL_CALLBACK_DATA,
+    )]])
+}
+
+async fn reroll(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    telegram_send: mpsc::UnboundedSender<TelegramControlCommand>,
+    cf_client: Arc<codeforces::Client>,
+    msg: Message,
+) -> Result<()> {
+    let sender = msg
+        .from()
+        .ok_or_else(|| miette!("Reroll command without sender"))?;
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    let now = unix_time_s()?;
+    let Some(daily_message_id) = state.current_daily_message_id() else {
+        return bot
+            .send_message(msg.chat.id, "There is no daily problem to reroll")
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    };
+    if state.running_reroll_vote(now).is_some() {
+        return bot
+            .send_message(msg.chat.id, "There already is a running reroll vote")
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    }
+
+    if state.reroll_settings.quorum <= 1 {
+        state.reroll_vote = None;
+        dialogue.update(state).await.into_diagnostic()?;
+        bot.send_message(msg.chat.id, "Rerolling today's problem")
+            .await
+            .into_diagnostic()?;
+        return replace_daily_problems(&bot, &telegram_send, &cf_client, msg.chat.id).await;
+    }
+
+    let mut vote = RerollVote {
+        message_id: msg.id,
+        daily_message_id,
+        voters: BTreeSet::from([sender.id]),
+        started_at: now,
+    };
+    let vote_message = bot
+        .send_message(msg.chat.id, state.reroll_vote_text(&vote))
+        .reply_markup(reroll_keyboard())
+        .await
+        .into_diagnostic()?;
+    vote.message_id = vote_message.id;
+    state.reroll_vote = Some(vote.clone());
+    let limit = Duration::from_secs(state.reroll_settings.duration_s());
+    dialogue.update(state).await.into_diagnostic()?;
+
+    // once the time is up, only the message is updated, since changing the state here could
+    // overwrite other updates of the chat. The expired vote is dropped by the next vote or reroll
+    tokio::spawn(async move {
+        tokio::time::sleep(limit).await;
+        if let Err(report) = show_reroll_vote_over(&bot, &dialogue, vote.message_id).await {
+            log::error!(
+                "Could not close reroll vote in {:?}\n{}",
+                dialogue.chat_id(),
+                report
+            );
+        }
+    });
+    Ok(())
+}
+
+/// Shows that the vote with the given message is over, if it did not succeed before
+async fn show_reroll_vote_over(
+    bot: &Bot,
+    dialogue: &MyDialogue,
+    message_id: MessageId,
+) -> Result<()> {
+    let state = dialogue.get_or_default().await.into_diagnostic()?;
+    let Some(vote) = state
+        .reroll_vote
+        .as_ref()
+        .filter(|vote| vote.message_id == message_id)
+    else {
+        return Ok(());
+    };
+    bot.edit_message_text(
+        dialogue.chat_id(),
+        message_id,
+        state.reroll_vote_over_text(vote),
+    )
+    .await
+    .into_diagnostic()
+    .map(|_| ())
+}
+
+async fn vote_reroll(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    telegram_send: mpsc::UnboundedSender<TelegramControlCommand>,
+    cf_client: Arc<codeforces::Client>,
+    query: CallbackQuery,
+) -> Result<()> {
+    bot.answer_callback_query(query.id.clone())
+        .await
+        .into_diagnostic()?;
+    let Some(message) = query.message else {
+        return Ok(());
+    };
+
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    let is_running = state
+        .running_reroll_vote(unix_time_s()?)
+        .is_some_and(|vote| vote.message_id == message.id);
+    if !is_running {
+        // the vote is over, but its message was not updated
+        let text = match state
+            .reroll_vote
+            .take_if(|vote| vote.message_id == message.id)
+        {
+            Some(vote) => {
+                let text = state.reroll_vote_over_text(&vote);
+                dialogue.update(state).await.into_diagnostic()?;
+                text
+            }
+            None => String::from("This reroll vote is over"),
+        };
+        return bot
+            .edit_message_text(message.chat.id, message.id, text)
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    }
+    let Some(vote) = state.reroll_vote.as_mut() else {
+        return Ok(());
+    };
+    if !vote.voters.insert(query.from.id) {
+        return Ok(());
+    }
+
+    if vote.voters.len() >= state.reroll_settings.quorum {
+        state.reroll_vote = None;
+        dialogue.update(state).await.into_diagnostic()?;
+        bot.edit_message_text(
+            message.chat.id,
+            message.id,
+            "The reroll vote succeeded, rerolling today's problem",
+        )
+        .await
+        .into_diagnostic()?;
+        replace_daily_problems(&bot, &telegram_send, &cf_client, message.chat.id).await
+    } else {
+        let vote = vote.clone();
+        let text = state.reroll_vote_text(&vote);
+        dialogue.update(state).await.into_diagnostic()?;
+        bot.edit_message_text(message.chat.id, message.id, text)
+            .reply_markup(reroll_keyboard())
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    }
+}
+
+async fn set_reroll_settings(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetReroll { quorum, minutes } = command {
+        if quorum == 0 || !(1..=RerollSettings::MAX_MINUTES).contains(&minutes) {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    format!(
+                        "Quorum and minutes should be at least 1, and there can be at most {} minutes",
+                        RerollSettings::MAX_MINUTES
+                    ),
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        }
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.reroll_settings = RerollSettings { quorum, minutes };
+        dialogue.update(state).await.into_diagnostic()?;
+
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Today's problem is replaced if {} users vote for it within {} minutes",
+                quorum, minutes
+            ),
+        )
+        .await
+        .into_diagnostic()
+        .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-reroll command did not receive correct data"
+        ))
+    }
+}
+
+async fn preview(
+    bot: Arc<Bot>,
+    telegram_send: mpsc::UnboundedSender<TelegramControlCommand>,
+    cf_client: Arc<codeforces::Client>,
+    msg: Message,
+) -> Result<()> {
+    let channel_state = util::get_channel_state(msg.chat.id, &telegram_send).await?;
+    let tomorrow = channel_state
+        .today()
+        .succ_opt()
+        .ok_or_else(|| miette!("There is no day after today"))?;
+
+    let message_str = match channel_state
+        .find_daily_problems(cf_client.as_ref(), msg.chat.id, tomorrow)
+        .await?
+    {
+        Some(problems) => {
+            let mut result = if channel_state.posted_daily_message_today() {
+                format!(
+                    "Unless the configuration or the submissions change, the problems on {} will be:",
+                    tomorrow.format("%Y-%m-%d")
+                )
+            } else {
+                // the problems of today's message will count as given once it is posted
+                format!(
+                    "Today's daily message is not posted yet, and its problems may change this preview. \
+                    Otherwise, unless the configuration or the submissions change, the problems on {} will be:",
+                    tomorrow.format("%Y-%m-%d")
+                )
+            };
+            for (tier, problem) in problems {
+                result.push('\n');
+                if let Some(tier) = tier {
+                    result.push_str(&tier);
+                    result.push_str(": ");
+                }
+                result.push_str(&problem.url()?);
+            }
+            result
+        }
+        None => {
+            channel_state
+                .no_daily_problem_text(cf_client.as_ref())
+                .await
+        }
+    };
+
+    bot.send_message(msg.chat.id, message_str)
+        .await
+        .into_diagnostic()
+        .map(|_| ())
+}
+
+async fn help(bot: Arc<Bot>, msg: Message) -> Result<()> {
+    bot.send_message(msg.chat.id, ChannelCommand::descriptions().to_string())
+        .await
+        .into_diagnostic()?;
+    Ok(())
+}
+
+async fn is_admin(bot: &Bot, chat: &Chat, user_id: UserId) -> Result<bool> {
+    if chat.is_private() {
+        return Ok(true);
+    }
+    Ok(bot
+        .get_chat_member(chat.id, user_id)
+        .await
+        .into_diagnostic()?
+        .is_privileged())
+}
+
+async fn is_authorized(
+    bot: &Bot,
+    state: &ChannelState,
+    chat: &Chat,
+    user_id: UserId,
+) -> Result<bool> {
+    Ok(state.allowed_users.contains(&user_id) || is_admin(bot, chat, user_id).await?)
+}
+
+/// What the sender of a message may change in its chat
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Permission {
+    /// Chat admins may change the configuration and allow other users to do so
+    Admin,
+    /// Allowed users may change the configuration
+    Allowed,
+    Refused,
+}
+
+/// Resolves the permission of the sender once per update, as it needs a request to Telegram
+async fn sender_permission(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    msg: Message,
+) -> Option<Permission> {
+    let Some(sender) = msg.from() else {
+        return Some(Permission::Refused);
+    };
+    match is_admin(&bot, &msg.chat, sender.id).await {
+        Ok(true) => return Some(Permission::Admin),
+        Ok(false) => {}
+        Err(report) => log::error!(
+            "Could not check admin status of {:?}\n{}",
+            sender.id,
+            report
+        ),
+    }
+    match dialogue.get_or_default().await {
+        Ok(state) if state.allowed_users.contains(&sender.id) => Some(Permission::Allowed),
+        Ok(_) => Some(Permission::Refused),
+        Err(err) => {
+            log::error!("Could not get channel state of {:?}\n{}", msg.chat.id, err);
+            Some(Permission::Refused)
+        }
+    }
+}
+
+async fn refuse(bot: Arc<Bot>, msg: Message) -> Result<()> {
+    bot.send_message(
+        msg.chat.id,
+        "Only chat admins and allowed users can change the configuration of this chat",
+    )
+    .await
+    .into_diagnostic()?;
+    Ok(())
+}
+
+async fn allow(bot: Arc<Bot>, dialogue: MyDialogue, msg: Message) -> Result<()> {
+    let Some(user) = msg.reply_to_message().and_then(|reply| reply.from()) else {
+        return bot
+            .send_message(
+                msg.chat.id,
+                "Reply to a message of the user you want to allow",
+            )
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    };
+
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    state.allowed_users.insert(user.id);
+    dialogue.update(state).await.into_diagnostic()?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("{} may now change the configuration", user.full_name()),
+    )
+    .await
+    .into_diagnostic()?;
+    Ok(())
+}
+
+async fn disallow(bot: Arc<Bot>, dialogue: MyDialogue, msg: Message) -> Result<()> {
+    let Some(user) = msg.reply_to_message().and_then(|reply| reply.from()) else {
+        return bot
+            .send_message(
+                msg.chat.id,
+                "Reply to a message of the user you want to disallow",
+            )
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    };
+
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    state.allowed_users.remove(&user.id);
+    dialogue.update(state).await.into_diagnostic()?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "{} may no longer change the configuration",
+            user.full_name()
+        ),
+    )
+    .await
+    .into_diagnostic()?;
+    Ok(())
+}
+
+/// Asks the user to prove ownership of the handle by submitting a compilation error to a random problem.
+///
+/// The handle is only registered once the user sends /verify.
+async fn request_verification(
+    bot: &Bot,
+    dialogue: MyDialogue,
+    options: &Options,
+    cf_client: &codeforces::Client,
+    msg: &Message,
+    user: &User,
+    codeforces_handle: &str,
+) -> Result<()> {
+    let Some(handle) =
+        codeforces::Handle::from_checked(String::from(codeforces_handle), cf_client).await
+    else {
+        return bot
+            .send_message(
+                msg.chat.id,
+                format!("{} is no valid Codeforces Handle", codeforces_handle),
+            )
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    };
+
+    let problem = {
+        let problem_index = cf_client.problem_index().await?;
+        let problems: Vec<_> = problem_index
+            .problems()
+            .iter()
+            .map(|indexed_problem| &indexed_problem.problem)
+            .filter(|problem| problem.contest_id.is_some())
+            .collect();
+        miette::ensure!(!problems.is_empty(), "Codeforces returned no problems");
+        let mut rng: Xorshift128 = {
+            let states = [unix_time_s()? as u64, user.id.0];
+            SeedableRng::from_seed(&states[..])
+        };
+        problems[(rng.next_u64() as usize) % problems.len()].clone()
+    };
+    let message_str = format!(
+        "{}, to verify that you own {}, submit a solution with a compilation error to {} within {} minutes. Then send /verify",
+        user.full_name(),
+        handle.as_str(),
+        problem.url()?,
+        options.verification_minutes,
+    );
+
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    state.pending_verifications.insert(
+        user.id,
+        PendingVerification {
+            display_name: user.full_name(),
+            handle,
+            problem,
+            requested_at: unix_time_s()?,
+        },
+    );
+    dialogue.update(state).await.into_diagnostic()?;
+
+    bot.send_message(msg.chat.id, message_str)
+        .await
+        .into_diagnostic()
+        .map(|_| ())
+}
+
+async fn me(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    options: Arc<Options>,
+    cf_client: Arc<codeforces::Client>,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::Me { codeforces_handle } = command {
+        let sender = msg
+            .from()
+            .ok_or_else(|| miette!("Register command without sender"))?;
+        request_verification(
+            bot.as_ref(),
+            dialogue,
+            options.as_ref(),
+            cf_client.as_ref(),
+            &msg,
+            sender,
+            codeforces_handle.trim(),
+        )
+        .await
+    } else {
+        Err(miette!(
+            "Handler for me command did not receive correct data"
+        ))
+    }
+}
+
+async fn verify(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    options: Arc<Options>,
+    cf_client: Arc<codeforces::Client>,
+    msg: Message,
+) -> Result<()> {
+    let sender = msg
+        .from()
+        .ok_or_else(|| miette!("Verify command without sender"))?;
+    let state = dialogue.get_or_default().await.into_diagnostic()?;
+    let Some(verification) = state.pending_verifications.get(&sender.id).cloned() else {
+        return bot
+            .send_message(
+                msg.chat.id,
+                "There is no pending verification for you. Use /me to start one",
+            )
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    };
+
+    let deadline = i64::try_from(options.verification_minutes)
+        .ok()
+        .and_then(|minutes| minutes.checked_mul(60))
+        .and_then(|seconds| verification.requested_at.checked_add(seconds))
+        .ok_or_else(|| miette!("Verification deadline out of range"))?;
+    let problem_identifier = verification.problem.identifier()?;
+    let submissions = verification
+        .handle
+        .get_recent_submissions(cf_client.as_ref(), VERIFICATION_SUBMISSION_COUNT)
+        .await?;
+    let verified = submissions.iter().any(|submission| {
+        submission.verdict == Some(codeforces::Verdict::CompilationError)
+            && (verification.requested_at..=deadline).contains(&submission.creation_time_seconds)
+            && submission.problem.identifier().ok().as_ref() == Some(&problem_identifier)
+    });
+
+    // the state may have changed while fetching the submissions
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    if state.pending_verifications.get(&sender.id) != Some(&verification) {
+        return bot
+            .send_message(
+                msg.chat.id,
+                "Your verification changed meanwhile. Send /verify again",
+            )
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    }
+    let message_str = if verified {
+        state.register_user(sender.id, verification.display_name, verification.handle);
+        state.registrations_text()
+    } else if unix_time_s()? > deadline {
+        state.pending_verifications.remove(&sender.id);
+        String::from("The verification expired. Use /me to start a new one")
+    } else {
+        format!(
+            "No compilation error to {} found yet. Submit one and send /verify again",
+            verification.problem.url()?
+        )
+    };
+    dialogue.update(state).await.into_diagnostic()?;
+
+    bot.send_message(msg.chat.id, message_str)
+        .await
+        .into_diagnostic()
+        .map(|_| ())
+}
+
+async fn register(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    options: Arc<Options>,
+    cf_client: Arc<codeforces::Client>,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::Register { codeforces_handle } = command {
+        let Some(user) = msg.reply_to_message().and_then(|reply| reply.from()) else {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    "Reply to a message of the user you want to register, or use /me to register yourself",
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        };
+
+        request_verification(
+            bot.as_ref(),
+            dialogue,
+            options.as_ref(),
+            cf_client.as_ref(),
+            &msg,
+            user,
+            codeforces_handle.trim(),
+        )
+        .await
+    } else {
+        Err(miette!(
+            "Handler for register command did not receive correct data"
+        ))
+    }
+}
+
+async fn unregister(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::Unregister { display_name } = command {
+        let sender = msg
+            .from()
+            .ok_or_else(|| miette!("Unregister command without sender"))?;
+        let display_name = display_name.trim();
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+
+        // determine whom to unregister: a legacy registration, or a Telegram user
+        let target = if !display_name.is_empty() {
+            if state.legacy_registered_users.contains_key(display_name) {
+                None
+            } else {
+                match state
+                    .registered_users
+                    .iter()
+                    .find(|(_, user)| user.display_name == display_name)
+                {
+                    Some((user_id, _)) => Some(*user_id),
+                    None => {
+                        return bot
+                            .send_message(
+                                msg.chat.id,
+                                format!("{} is not registered", display_name),
+                            )
+                            .await
+                            .into_diagnostic()
+                            .map(|_| ());
+                    }
+                }
+            }
+        } else if let Some(user) = msg.reply_to_message().and_then(|reply| reply.from()) {
+            Some(user.id)
+        } else {
+            Some(sender.id)
+        };
+
+        if target != Some(sender.id) && !is_authorized(&bot, &state, &msg.chat, sender.id).await? {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    "Only chat admins and allowed users can unregister other users",
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        }
+
+        let removed_name = match target {
+            Some(user_id) => state
+                .registered_users
+                .remove(&user_id)
+                .map(|user| user.display_name),
+            None => state
+                .legacy_registered_users
+                .remove(display_name)
+                .map(|_| String::from(display_name)),
+        };
+        let message_str = match removed_name {
+            Some(removed_name) => {
+                let result = format!(
+                    "Unregistered {}\n\n{}",
+                    removed_name,
+                    state.registrations_text()
+                );
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            None => String::from("This user is not registered"),
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for unregister command did not receive correct data"
+        ))
+    }
+}
+
+async fn users(bot: Arc<Bot>, dialogue: MyDialogue, msg: Message) -> Result<()> {
+    let state = dialogue.get_or_default().await.into_diagnostic()?;
+    bot.send_message(msg.chat.id, state.registrations_text())
+        .await
+        .into_diagnostic()?;
+    Ok(())
+}
+
+async fn set_rating_range(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetRatingRange {
+        lower_bound,
+        upper_bound,
+    } = command
+    {
+        if lower_bound <= upper_bound {
+            let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+            state.rating_range = Some(lower_bound..=upper_bound);
+            state.auto_rating_range = None;
+            dialogue.update(state).await.into_diagnostic()?;
+
+            bot.send_message(msg.chat.id, "Updated rating range")
+                .await
+                .into_diagnostic()
+                .map(|_| ())
+        } else {
+            bot.send_message(msg.chat.id, "Lower bound should not exceed upper bound")
+                .await
+                .into_diagnostic()
+                .map(|_| ())
+        }
+    } else {
+        Err(miette!(
+            "Handler for set-rating command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_auto_rating_range(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    cf_client: Arc<codeforces::Client>,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetAutoRatingRange { offset, width } = command {
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.auto_rating_range = Some(AutoRatingRange { offset, width });
+        // store before fetching, so no update made meanwhile is overwritten
+        dialogue.update(state.clone()).await.into_diagnostic()?;
+        let rating_range = state.target_rating_range(cf_client.as_ref()).await;
+
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "The rating range now follows the registered users, currently {} to {}",
+                rating_range.start(),
+                rating_range.end()
+            ),
+        )
+        .await
+        .into_diagnostic()
+        .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for auto-range command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_rating_widening(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetRatingWidening { step, max_steps } = command {
+        if step > RatingWidening::MAX_STEP || max_steps > RatingWidening::MAX_STEPS {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    format!(
+                        "The step can be at most {} and there can be at most {} steps",
+                        RatingWidening::MAX_STEP,
+                        RatingWidening::MAX_STEPS
+                    ),
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        }
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.rating_widening = RatingWidening { step, max_steps };
+        dialogue.update(state).await.into_diagnostic()?;
+
+        bot.send_message(msg.chat.id, "Updated rating range widening")
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-widening command did not receive correct data"
+        ))
+    }
+}
+
+/// Parses a comma separated list of tiers of the form `name lower-upper`, or describes the first invalid one
+fn parse_difficulty_tiers(tiers_str: &str) -> std::result::Result<Vec<DifficultyTier>, String> {
+    let mut tiers: Vec<DifficultyTier> = Vec::new();
+    for tier_str in tiers_str
+        .split(',')
+        .map(str::trim)
+        .filter(|tier_str| !tier_str.is_empty())
+    {
+        let invalid = || format!("{} is not of the form name lower-upper", tier_str);
+        let (name, range) = tier_str
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(invalid)?;
+        let (lower_bound, upper_bound) = range.split_once('-').ok_or_else(invalid)?;
+        let (Ok(lower_bound), Ok(upper_bound)) = (
+            lower_bound.trim().parse::<u64>(),
+            upper_bound.trim().parse::<u64>(),
+        ) else {
+            return Err(invalid());
+        };
+        let name = name.trim();
+        if lower_bound > upper_bound {
+            return Err(format!(
+                "Lower bound of {} should not exceed upper bound",
+                name
+            ));
+        }
+        if tiers.iter().any(|tier| tier.name == name) {
+            return Err(format!("There are several tiers named {}", name));
+        }
+        tiers.push(DifficultyTier {
+            name: String::from(name),
+            rating_range: lower_bound..=upper_bound,
+        });
+    }
+    Ok(tiers)
+}
+
+async fn set_difficulty_tiers(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetDifficultyTiers { tiers } = command {
+        let message_str = match parse_difficulty_tiers(&tiers) {
+            Ok(tiers) => {
+                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+                state.difficulty_tiers = tiers;
+                let result = state.tiers_text();
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            Err(error_str) => error_str,
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-tiers command did not receive correct data"
+        ))
+    }
+}
+
+/// Parses a comma separated list of Codeforces tags, or describes the first unknown tag
+fn parse_tags(tags_str: &str) -> std::result::Result<Vec<String>, String> {
+    tags_str
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| {
+            let tag = tag.to_lowercase();
+            if codeforces::TAGS.contains(&tag.as_str()) {
+                Ok(tag)
+            } else {
+                Err(format!("{} is no known tag", tag))
+            }
+        })
+        .collect()
+}
+
+async fn include_tags(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::IncludeTags { tags } = command {
+        let message_str = match parse_tags(&tags) {
+            Ok(tags) => {
+                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+                state.tag_preferences.included = tags.into_iter().collect();
+                let result = state.tags_text();
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            Err(error_str) => error_str,
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for include-tags command did not receive correct data"
+        ))
+    }
+}
+
+async fn exclude_tags(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::ExcludeTags { tags } = command {
+        let message_str = match parse_tags(&tags) {
+            Ok(tags) => {
+                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+                state.tag_preferences.excluded = tags.into_iter().collect();
+                let result = state.tags_text();
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            Err(error_str) => error_str,
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for exclude-tags command did not receive correct data"
+        ))
+    }
+}
+
+async fn tag_weights(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::TagWeights { weights } = command {
+        let weights: std::result::Result<BTreeMap<_, _>, _> = weights
+            .split(',')
+            .map(str::trim)
+            .filter(|weight_str| !weight_str.is_empty())
+            .map(|weight_str| {
+                let (tag, weight) = weight_str
+                    .split_once('=')
+                    .ok_or_else(|| format!("{} is not of the form tag=weight", weight_str))?;
+                let weight = weight
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|weight| *weight <= MAX_TAG_WEIGHT)
+                    .ok_or_else(|| {
+                        format!(
+                            "{} is no valid weight, weights are at most {}",
+                            weight.trim(),
+                            MAX_TAG_WEIGHT
+                        )
+                    })?;
+                let tag = parse_tags(tag)?
+                    .pop()
+                    .ok_or_else(|| format!("{} is not of the form tag=weight", weight_str))?;
+                Ok((tag, weight))
+            })
+            .collect();
+
+        let message_str = match weights {
+            Ok(weights) => {
+                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+                state.tag_preferences.weights = weights;
+                let result = state.tags_text();
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            Err(error_str) => error_str,
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for tag-weights command did not receive correct data"
+        ))
+    }
+}
+
+/// Parses the argument of commands taking a single number, which the split parser cannot do
+fn parse_single_number<T: std::str::FromStr>(input: String) -> std::result::Result<(T,), ParseError>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    input
+        .trim()
+        .parse()
+        .map(|number| (number,))
+        .map_err(|err| ParseError::IncorrectFormat(Box::new(err)))
+}
+
+async fn set_tag_rotation(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetTagRotation { days } = command {
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.set_tag_rotation(TagRotation { days });
+        let message_str = state.tags_text();
+        dialogue.update(state).await.into_diagnostic()?;
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-rotation command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_contest_kinds(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetContestKinds { kinds } = command {
+        let kinds: std::result::Result<_, _> = kinds
+            .split(',')
+            .map(str::trim)
+            .filter(|kind| !kind.is_empty())
+            .map(|kind| {
+                codeforces::ContestKind::ALL
+                    .into_iter()
+                    .find(|contest_kind| contest_kind.name() == kind.to_lowercase())
+                    .ok_or_else(|| format!("{} is no known contest kind", kind))
+            })
+            .collect();
+
+        let message_str = match kinds {
+            Ok(kinds) => {
+                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+                state.contest_filter.kinds = kinds;
+                let result = state.contest_filter.text();
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            Err(error_str) => error_str,
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-divisions command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_min_contest_year(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetMinContestYear { year } = command {
+        let year = year.trim();
+        let min_year = if year.is_empty() {
+            None
+        } else {
+            let Ok(year) = year.parse::<i32>() else {
+                return bot
+                    .send_message(msg.chat.id, format!("{} is no valid year", year))
+                    .await
+                    .into_diagnostic()
+                    .map(|_| ());
+            };
+            Some(year)
+        };
+
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.contest_filter.min_year = min_year;
+        let message_str = state.contest_filter.text();
+        dialogue.update(state).await.into_diagnostic()?;
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-min-year command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_solved_count(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetSolvedCount { bounds } = command {
+        let bounds = bounds.trim();
+        let parse_bound = |bound: &str| {
+            let bound = bound.trim();
+            if bound.is_empty() {
+                Ok(None)
+            } else {
+                bound.parse::<u64>().map(Some)
+            }
+        };
+        let (min_solved_count, max_solved_count) = if bounds.is_empty() {
+            (None, None)
+        } else {
+            match bounds
+                .split_once('-')
+                .map(|(min, max)| (parse_bound(min), parse_bound(max)))
+            {
+                Some((Ok(min), Ok(max))) if min.zip(max).is_none_or(|(min, max)| min <= max) => {
+                    (min, max)
+                }
+                _ => {
+                    return bot
+                        .send_message(
+                            msg.chat.id,
+                            format!(
+                                "{} is not of the form [min]-[max] with min not exceeding max",
+                                bounds
+                            ),
+                        )
+                        .await
+                        .into_diagnostic()
+                        .map(|_| ());
+                }
+            }
+        };
+
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.popularity.min_solved_count = min_solved_count;
+        state.popularity.max_solved_count = max_solved_count;
+        let message_str = state.popularity.text();
+        dialogue.update(state).await.into_diagnostic()?;
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-solved command did not receive correct data"
+        ))
+    }
 }
 
-#[cfg(not(feature = "persistent"))]
-pub type MyStorage = teloxide::dispatching::dialogue::InMemStorage<ChannelState>;
-#[cfg(feature = "persistent")]
-pub type MyStorage = teloxide::dispatching::dialogue::RedisStorage<
-    teloxide::dispatching::dialogue::serializer::Json,
->;
+async fn set_popularity(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::Popularity { popularity } = command {
+        let popularity = popularity.trim().to_lowercase();
+        let Some(popularity) = Popularity::ALL
+            .into_iter()
+            .find(|preferred| preferred.name() == popularity)
+        else {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    format!("{} is none of any, classic or obscure", popularity),
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        };
 
-pub type MyDialogue = Dialogue<ChannelState, MyStorage>;
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.popularity.preferred = popularity;
+        let message_str = state.popularity.text();
+        dialogue.update(state).await.into_diagnostic()?;
 
-async fn start(
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for popularity command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_review_mode(
     bot: Arc<Bot>,
-    sched_send: mpsc::UnboundedSender<SchedulerControlCommand>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
     msg: Message,
 ) -> Result<()> {
-    sched_send
-        .send(SchedulerControlCommand::StartDailyMessages {
-            chat_id: msg.chat.id,
-        })
-        .into_diagnostic()?;
-    bot.send_message(msg.chat.id, "A daily problem will be prepared for you🍴")
+    if let ChannelCommand::Review { mode } = command {
+        let mode = mode.trim().to_lowercase();
+        let Some(review_mode) = ReviewMode::ALL
+            .into_iter()
+            .find(|review_mode| review_mode.name() == mode)
+        else {
+            return bot
+                .send_message(msg.chat.id, format!("{} is none of off, chat or dm", mode))
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        };
+
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.review_mode = review_mode;
+        if review_mode == ReviewMode::Off {
+            state.review_items.clear();
+        }
+        dialogue.update(state).await.into_diagnostic()?;
+
+        let message_str = match review_mode {
+            ReviewMode::Off => "Failed daily problems are not reviewed",
+            ReviewMode::Chat => "Failed daily problems are posted again in this chat for review",
+            ReviewMode::Direct => {
+                "Failed daily problems are sent again for review to those who failed them. Start a chat with me to receive them"
+            }
+        };
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for review command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_upsolving(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::Upsolve { settings } = command {
+        let mut words = settings.split_whitespace();
+        let upsolving = match (words.next(), words.next(), words.next()) {
+            (Some("off"), None, None) => Ok(None),
+            (Some(min_participants), max_age_days, None) => {
+                match (
+                    min_participants.parse::<usize>(),
+                    max_age_days.map_or(Ok(DEFAULT_UPSOLVING_DAYS), str::parse::<u64>),
+                ) {
+                    (Ok(min_participants), Ok(max_age_days))
+                        if min_participants > 0 && max_age_days <= Upsolving::MAX_AGE_DAYS =>
+                    {
+                        Ok(Some(Upsolving {
+                            min_participants,
+                            max_age_days,
+                        }))
+                    }
+                    _ => Err(()),
+                }
+            }
+            _ => Err(()),
+        };
+
+        let message_str = match upsolving {
+            Ok(upsolving) => {
+                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+                let result = match &upsolving {
+                    Some(upsolving) => format!(
+                        "Daily problems are taken from contests at least {} registered users took part in within the last {} days, if there are any",
+                        upsolving.min_participants, upsolving.max_age_days
+                    ),
+                    None => String::from("Daily problems are no longer taken from recent contests"),
+                };
+                state.upsolving = upsolving;
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            Err(()) => format!(
+                "Usage: /upsolve <off|min-participants> [max-age-days], with at least one participant and at most {} days",
+                Upsolving::MAX_AGE_DAYS
+            ),
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for upsolve command did not receive correct data"
+        ))
+    }
+}
+
+async fn set_known_problem_policy(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::Known { policy } = command {
+        let words: Vec<_> = policy.split_whitespace().collect();
+        let policy = match words.as_slice() {
+            ["attempted"] => Some(KnownProblemPolicy::Attempted),
+            ["solved"] => Some(KnownProblemPolicy::Solved),
+            ["solved", min_solvers] => match min_solvers.parse::<usize>() {
+                Ok(min_solvers) if min_solvers > 0 => {
+                    Some(KnownProblemPolicy::SolvedBy(min_solvers))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some(policy) = policy else {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    "Usage: /known <attempted|solved|solved k>, with k at least 1",
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        };
+
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.known_problem_policy = policy;
+        dialogue.update(state).await.into_diagnostic()?;
+
+        bot.send_message(
+            msg.chat.id,
+            format!("Only problems {} are chosen", policy.unknown_text()),
+        )
+        .await
+        .into_diagnostic()
+        .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for known command did not receive correct data"
+        ))
+    }
+}
+
+async fn tags(bot: Arc<Bot>, dialogue: MyDialogue, msg: Message) -> Result<()> {
+    let state = dialogue.get_or_default().await.into_diagnostic()?;
+    bot.send_message(msg.chat.id, state.tags_text())
         .await
         .into_diagnostic()?;
     Ok(())
 }
 
-async fn new_problem(
+async fn set_time(
     bot: Arc<Bot>,
-    telegram_send: mpsc::UnboundedSender<TelegramControlCommand>,
-    cf_client: Arc<codeforces::Client>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    sched_send: mpsc::UnboundedSender<SchedulerControlCommand>,
     msg: Message,
 ) -> Result<()> {
-    let channel_state = util::get_channel_state(msg.chat.id, &telegram_send).await?;
+    if let ChannelCommand::SetTime { time, timezone } = command {
+        let Ok(time) = NaiveTime::parse_from_str(&time, "%H:%M") else {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    format!("{} is no valid time of the form hh:mm", time),
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        };
+        let Ok(timezone) = timezone.parse::<chrono_tz::Tz>() else {
+            return bot
+                .send_message(
+                    msg.chat.id,
+                    format!("{} is no valid IANA timezone", timezone),
+                )
+                .await
+                .into_diagnostic()
+                .map(|_| ());
+        };
+
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        state.message_schedule = Some(MessageSchedule {
+            cron: format!("0 {} {} * * * *", time.minute(), time.hour()),
+            timezone,
+        });
+        dialogue.update(state).await.into_diagnostic()?;
 
-    bot.send_message(msg.chat.id, "Forcing a new daily problem")
+        sched_send
+            .send(SchedulerControlCommand::RescheduleDailyMessages {
+                chat_id: msg.chat.id,
+            })
+            .into_diagnostic()?;
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "Daily messages will be sent at {} ({})",
+                time.format("%H:%M"),
+                timezone.name()
+            ),
+        )
         .await
-        .into_diagnostic()?;
-    telegram_send
-        .send(TelegramControlCommand::SetAndNotifyDailyProblem {
-            chat_id: msg.chat.id,
-            problem: channel_state
-                .find_daily_problem(cf_client.as_ref(), msg.chat.id)
-                .await?,
-        })
         .into_diagnostic()
+        .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-time command did not receive correct data"
+        ))
+    }
 }
 
-async fn help(bot: Arc<Bot>, msg: Message) -> Result<()> {
-    bot.send_message(msg.chat.id, ChannelCommand::descriptions().to_string())
+/// Day of a planned theme
+enum ThemeDay {
+    Weekday(Weekday),
+    Date(NaiveDate),
+}
+
+/// Parses `<weekday|yyyy-mm-dd> [tag, ...] [lower-upper]`, or describes why it is invalid
+fn parse_theme(theme_str: &str) -> std::result::Result<(ThemeDay, Option<ThemeRule>), String> {
+    let theme_str = theme_str.trim();
+    let (day, rule) = theme_str
+        .split_once(char::is_whitespace)
+        .unwrap_or((theme_str, ""));
+    let day = if let Ok(weekday) = day.parse::<Weekday>() {
+        ThemeDay::Weekday(weekday)
+    } else if let Ok(date) = NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+        ThemeDay::Date(date)
+    } else {
+        return Err(format!(
+            "{} is neither a weekday nor a date of the form yyyy-mm-dd",
+            day
+        ));
+    };
+
+    let rule = rule.trim();
+    if rule.is_empty() {
+        return Ok((day, None));
+    }
+    // the range is the last word, unless it belongs to a tag like 2-sat
+    let (tags, range) = rule.rsplit_once(char::is_whitespace).unwrap_or(("", rule));
+    let bounds = range
+        .split_once('-')
+        .map(|(lower_bound, upper_bound)| (lower_bound.parse::<u64>(), upper_bound.parse::<u64>()));
+    let (tags, rating_range) = match bounds {
+        Some((Ok(lower_bound), Ok(upper_bound))) if lower_bound <= upper_bound => {
+            (tags, Some(lower_bound..=upper_bound))
+        }
+        Some((Ok(_), Ok(_))) => {
+            return Err(String::from("Lower bound should not exceed upper bound"));
+        }
+        _ => (rule, None),
+    };
+
+    Ok((
+        day,
+        Some(ThemeRule {
+            tags: parse_tags(tags)?.into_iter().collect(),
+            rating_range,
+        }),
+    ))
+}
+
+async fn set_theme(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::SetTheme { theme } = command {
+        let message_str = match parse_theme(&theme) {
+            Ok((day, rule)) => {
+                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+                // past dates are no longer needed
+                let today = state.today();
+                state.theme_plan.dates.retain(|date, _| *date >= today);
+                match (day, rule) {
+                    (ThemeDay::Weekday(weekday), Some(rule)) => {
+                        state.theme_plan.weekdays.insert(weekday, rule);
+                    }
+                    (ThemeDay::Weekday(weekday), None) => {
+                        state.theme_plan.weekdays.remove(&weekday);
+                    }
+                    (ThemeDay::Date(date), Some(rule)) => {
+                        state.theme_plan.dates.insert(date, rule);
+                    }
+                    (ThemeDay::Date(date), None) => {
+                        state.theme_plan.dates.remove(&date);
+                    }
+                }
+                let result = state.theme_plan.text();
+                dialogue.update(state).await.into_diagnostic()?;
+                result
+            }
+            Err(error_str) => error_str,
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
+    } else {
+        Err(miette!(
+            "Handler for set-theme command did not receive correct data"
+        ))
+    }
+}
+
+async fn themes(bot: Arc<Bot>, dialogue: MyDialogue, msg: Message) -> Result<()> {
+    let state = dialogue.get_or_default().await.into_diagnostic()?;
+    bot.send_message(msg.chat.id, state.theme_plan.text())
         .await
         .into_diagnostic()?;
     Ok(())
 }
 
-async fn register(
+/// Appends problems to the queue, skipping those already queued and those not in the problemset
+async fn enqueue_problems(
+    bot: &Bot,
+    dialogue: MyDialogue,
+    cf_client: &codeforces::Client,
+    msg: &Message,
+    problem_ids: Vec<codeforces::ProblemIdentifier>,
+) -> Result<()> {
+    if problem_ids.is_empty() {
+        return bot
+            .send_message(msg.chat.id, "Found no Codeforces problems to queue")
+            .await
+            .into_diagnostic()
+            .map(|_| ());
+    }
+
+    let problem_index = cf_client.problem_index().await?;
+    let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+    let mut queued_count = 0;
+    let mut unknown = Vec::new();
+    for problem_id in problem_ids {
+        let Some(indexed_problem) = problem_index.get(&problem_id) else {
+            unknown.push(String::from(problem_id.as_str()));
+            continue;
+        };
+        if !state.problem_queue.contains(&problem_id) {
+            state
+                .problem_by_identifier
+                .insert(problem_id.clone(), indexed_problem.problem.clone());
+            state.problem_queue.push_back(problem_id);
+            queued_count += 1;
+        }
+    }
+
+    let mut message_str = format!("Queued {} problems", queued_count);
+    if !unknown.is_empty() {
+        message_str.push_str(&format!(
+            ", these are not in the problemset: {}",
+            unknown.join(", ")
+        ));
+    }
+    message_str.push_str("\n\n");
+    message_str.push_str(&state.queue_text());
+    dialogue.update(state).await.into_diagnostic()?;
+
+    bot.send_message(msg.chat.id, message_str)
+        .await
+        .into_diagnostic()
+        .map(|_| ())
+}
+
+async fn enqueue(
     bot: Arc<Bot>,
     dialogue: MyDialogue,
     command: ChannelCommand,
     cf_client: Arc<codeforces::Client>,
     msg: Message,
 ) -> Result<()> {
-    if let ChannelCommand::Register {
-        display_name,
-        codeforces_handle,
-    } = command
-    {
-        if let Some(handle) =
-            codeforces::Handle::from_checked(codeforces_handle.clone(), cf_client.as_ref()).await
-        {
-            let message_str = {
-                // get and change storage
-                let mut state = dialogue.get_or_default().await.into_diagnostic()?;
-                state
-                    .registered_users
-                    .entry(display_name.clone())
-                    .and_modify(|old| *old = handle.clone())
-                    .or_insert(handle);
-
-                // use storage to create answer
-                let mut result = String::from("Current Registrations:\n");
-                for (display_name, codeforces_handle) in &state.registered_users {
-                    result.push_str("Name: ");
-                    result.push_str(display_name);
-                    result.push('\t');
-                    result.push_str("Handle: ");
-                    result.push_str(codeforces_handle.as_str());
-                    result.push('\n');
-                }
+    if let ChannelCommand::Enqueue { problems } = command {
+        enqueue_problems(
+            bot.as_ref(),
+            dialogue,
+            cf_client.as_ref(),
+            &msg,
+            problem_list::parse_text(&problems),
+        )
+        .await
+    } else {
+        Err(miette!(
+            "Handler for enqueue command did not receive correct data"
+        ))
+    }
+}
 
-                // save storage
-                dialogue.update(state).await.into_diagnostic()?;
+/// Whether the message is a document captioned with the enqueue command
+fn is_enqueue_document(msg: Message) -> bool {
+    msg.document().is_some()
+        && msg
+            .caption()
+            .and_then(|caption| caption.split_whitespace().next())
+            .is_some_and(|command| command == "/enqueue" || command.starts_with("/enqueue@"))
+}
 
-                result
-            };
+async fn enqueue_document(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    cf_client: Arc<codeforces::Client>,
+    msg: Message,
+) -> Result<()> {
+    let document = msg
+        .document()
+        .ok_or_else(|| miette!("Enqueue document handler did not receive a document"))?;
+    let file = bot.get_file(&document.file.id).await.into_diagnostic()?;
+    let mut content = Vec::new();
+    bot.download_file(&file.path, &mut content)
+        .await
+        .into_diagnostic()?;
 
-            bot.send_message(msg.chat.id, message_str)
-                .await
-                .into_diagnostic()?;
+    match problem_list::parse_document(document.file_name.as_deref(), &content) {
+        Ok(problem_ids) => {
+            enqueue_problems(
+                bot.as_ref(),
+                dialogue,
+                cf_client.as_ref(),
+                &msg,
+                problem_ids,
+            )
+            .await
+        }
+        Err(error_str) => bot
+            .send_message(msg.chat.id, error_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ()),
+    }
+}
+
+async fn queue(bot: Arc<Bot>, dialogue: MyDialogue, msg: Message) -> Result<()> {
+    let state = dialogue.get_or_default().await.into_diagnostic()?;
+    bot.send_message(msg.chat.id, state.queue_text())
+        .await
+        .into_diagnostic()?;
+    Ok(())
+}
 
-            Ok(())
+async fn move_queued(
+    bot: Arc<Bot>,
+    dialogue: MyDialogue,
+    command: ChannelCommand,
+    msg: Message,
+) -> Result<()> {
+    if let ChannelCommand::MoveQueued { from, to } = command {
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        let positions = 1..=state.problem_queue.len();
+        let message_str = if positions.contains(&from) && positions.contains(&to) {
+            let problem_id = state
+                .problem_queue
+                .remove(from - 1)
+                .ok_or_else(|| miette!("Queued problem at a valid position is missing"))?;
+            state.problem_queue.insert(to - 1, problem_id);
+            let result = state.queue_text();
+            dialogue.update(state).await.into_diagnostic()?;
+            result
         } else {
-            bot.send_message(
-                msg.chat.id,
-                format!("{} is no valid Codeforces Handle", codeforces_handle),
+            format!(
+                "Positions have to be between 1 and {}",
+                state.problem_queue.len()
             )
+        };
+
+        bot.send_message(msg.chat.id, message_str)
             .await
-            .into_diagnostic()?;
-            Ok(())
-        }
+            .into_diagnostic()
+            .map(|_| ())
     } else {
         Err(miette!(
-            "Handler for register command did not receive correct data"
+            "Handler for move-queued command did not receive correct data"
         ))
     }
 }
 
-async fn set_rating_range(
+async fn dequeue(
     bot: Arc<Bot>,
     dialogue: MyDialogue,
     command: ChannelCommand,
     msg: Message,
 ) -> Result<()> {
-    if let ChannelCommand::SetRatingRange {
-        lower_bound,
-        upper_bound,
-    } = command
-    {
-        if lower_bound <= upper_bound {
-            let mut state = dialogue.get_or_default().await.into_diagnostic()?;
-            state.rating_range = Some(lower_bound..=upper_bound);
+    if let ChannelCommand::Dequeue { position } = command {
+        let position = position.trim();
+        let mut state = dialogue.get_or_default().await.into_diagnostic()?;
+        let message_str = if position.is_empty() {
+            state.problem_queue.clear();
+            let result = state.queue_text();
             dialogue.update(state).await.into_diagnostic()?;
-
-            bot.send_message(msg.chat.id, "Updated rating range")
-                .await
-                .into_diagnostic()
-                .map(|_| ())
+            result
         } else {
-            bot.send_message(msg.chat.id, "Lower bound should not exceed upper bound")
-                .await
-                .into_diagnostic()
-                .map(|_| ())
-        }
+            match position.parse::<usize>() {
+                Ok(position) if (1..=state.problem_queue.len()).contains(&position) => {
+                    state.problem_queue.remove(position - 1);
+                    let result = state.queue_text();
+                    dialogue.update(state).await.into_diagnostic()?;
+                    result
+                }
+                _ => format!(
+                    "{} is no position between 1 and {}",
+                    position,
+                    state.problem_queue.len()
+                ),
+            }
+        };
+
+        bot.send_message(msg.chat.id, message_str)
+            .await
+            .into_diagnostic()
+            .map(|_| ())
     } else {
         Err(miette!(
-            "Handler for set-rating command did not receive correct data"
+            "Handler for dequeue command did not receive correct data"
         ))
     }
 }
 
+fn unix_time_s() -> Result<i64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .into_diagnostic()?
+        .as_secs() as i64)
+}
+
 fn schema() -> UpdateHandler<miette::Error> {
     use dptree::case;
 
-    let command_handler = teloxide::filter_command::<ChannelCommand, _>()
+    let admin_handler = dptree::entry()
+        .branch(case![ChannelCommand::Allow].endpoint(allow))
+        .branch(case![ChannelCommand::Disallow].endpoint(disallow));
+
+    let configuration_handler = dptree::entry()
         .branch(case![ChannelCommand::Start].endpoint(start))
-        .branch(case![ChannelCommand::Help].endpoint(help))
-        .branch(case![ChannelCommand::NewProblem].endpoint(new_problem))
+        .branch(case![ChannelCommand::Pause].endpoint(pause))
+        .branch(case![ChannelCommand::Resume].endpoint(resume))
+        .branch(case![ChannelCommand::Stop].endpoint(stop))
+        .branch(case![ChannelCommand::SetReroll { quorum, minutes }].endpoint(set_reroll_settings))
         .branch(
             case![ChannelCommand::SetRatingRange {
                 lower_bound,
@@ -199,20 +2085,82 @@ fn schema() -> UpdateHandler<miette::Error> {
             .endpoint(set_rating_range),
         )
         .branch(
-            case![ChannelCommand::Register {
-                display_name,
-                codeforces_handle
-            }]
-            .endpoint(register),
+            case![ChannelCommand::SetAutoRatingRange { offset, width }]
+                .endpoint(set_auto_rating_range),
+        )
+        .branch(
+            case![ChannelCommand::SetRatingWidening { step, max_steps }]
+                .endpoint(set_rating_widening),
+        )
+        .branch(case![ChannelCommand::SetDifficultyTiers { tiers }].endpoint(set_difficulty_tiers))
+        .branch(case![ChannelCommand::IncludeTags { tags }].endpoint(include_tags))
+        .branch(case![ChannelCommand::ExcludeTags { tags }].endpoint(exclude_tags))
+        .branch(case![ChannelCommand::TagWeights { weights }].endpoint(tag_weights))
+        .branch(case![ChannelCommand::SetTagRotation { days }].endpoint(set_tag_rotation))
+        .branch(case![ChannelCommand::SetTheme { theme }].endpoint(set_theme))
+        .branch(case![ChannelCommand::SetContestKinds { kinds }].endpoint(set_contest_kinds))
+        .branch(case![ChannelCommand::SetMinContestYear { year }].endpoint(set_min_contest_year))
+        .branch(case![ChannelCommand::SetSolvedCount { bounds }].endpoint(set_solved_count))
+        .branch(case![ChannelCommand::Popularity { popularity }].endpoint(set_popularity))
+        .branch(case![ChannelCommand::Review { mode }].endpoint(set_review_mode))
+        .branch(case![ChannelCommand::Upsolve { settings }].endpoint(set_upsolving))
+        .branch(case![ChannelCommand::Known { policy }].endpoint(set_known_problem_policy))
+        .branch(case![ChannelCommand::Enqueue { problems }].endpoint(enqueue))
+        .branch(case![ChannelCommand::MoveQueued { from, to }].endpoint(move_queued))
+        .branch(case![ChannelCommand::Dequeue { position }].endpoint(dequeue))
+        .branch(case![ChannelCommand::SetTime { time, timezone }].endpoint(set_time))
+        .branch(case![ChannelCommand::Register { codeforces_handle }].endpoint(register));
+
+    let command_handler = teloxide::filter_command::<ChannelCommand, _>()
+        .branch(case![ChannelCommand::Help].endpoint(help))
+        .branch(case![ChannelCommand::Me { codeforces_handle }].endpoint(me))
+        .branch(case![ChannelCommand::Verify].endpoint(verify))
+        .branch(case![ChannelCommand::Unregister { display_name }].endpoint(unregister))
+        .branch(case![ChannelCommand::Users].endpoint(users))
+        .branch(case![ChannelCommand::Tags].endpoint(tags))
+        .branch(case![ChannelCommand::Themes].endpoint(themes))
+        .branch(case![ChannelCommand::Queue].endpoint(queue))
+        .branch(case![ChannelCommand::Reroll].endpoint(reroll))
+        .branch(case![ChannelCommand::Preview].endpoint(preview))
+        // all remaining commands change the configuration and need permission
+        .branch(
+            dptree::filter_map_async(sender_permission)
+                .branch(
+                    dptree::filter(|permission: Permission| permission == Permission::Admin)
+                        .chain(admin_handler),
+                )
+                .branch(
+                    dptree::filter(|permission: Permission| permission != Permission::Refused)
+                        .chain(configuration_handler),
+                )
+                .branch(dptree::endpoint(refuse)),
         );
 
-    let message_handler = Update::filter_message().branch(command_handler);
+    // files to import can only carry the command in their caption
+    let document_handler = dptree::filter(is_enqueue_document)
+        .chain(dptree::filter_map_async(sender_permission))
+        .branch(
+            dptree::filter(|permission: Permission| permission != Permission::Refused)
+                .endpoint(enqueue_document),
+        )
+        .branch(dptree::endpoint(refuse));
+
+    let message_handler = Update::filter_message()
+        .branch(command_handler)
+        .branch(document_handler);
 
-    dialogue::enter::<Update, MyStorage, ChannelState, _>().branch(message_handler)
+    let callback_query_handler = Update::filter_callback_query()
+        .filter(|query: CallbackQuery| query.data.as_deref() == Some(REROLL_CALLBACK_DATA))
+        .endpoint(vote_reroll);
+
+    dialogue::enter::<Update, MyStorage, ChannelState, _>()
+        .branch(message_handler)
+        .branch(callback_query_handler)
 }
 
 pub async fn setup(
     bot: Arc<Bot>,
+    options: Arc<Options>,
     telegram_send: mpsc::UnboundedSender<TelegramControlCommand>,
     sched_send: mpsc::UnboundedSender<SchedulerControlCommand>,
     storage: Arc<MyStorage>,
@@ -220,7 +2168,13 @@ pub async fn setup(
 ) -> (ShutdownToken, JoinHandle<()>) {
     let mut dispatcher = Dispatcher::builder(bot, schema())
         // storage is an Arc<_>, so cloning it keeps the connection
-        .dependencies(dptree::deps![storage, telegram_send, sched_send, cf_client])
+        .dependencies(dptree::deps![
+            storage,
+            options,
+            telegram_send,
+            sched_send,
+            cf_client
+        ])
         .build();
 
     let shutdown_token = dispatcher.shutdown_token();
@@ -241,3 +2195,74 @@ pub async fn create_storage(options: &Options) -> Result<Arc<MyStorage>> {
     .await
     .into_diagnostic()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_difficulty_tiers_accepts_names_with_spaces() {
+        let tiers = parse_difficulty_tiers("easy 800-1200, very hard 2000-2400,").unwrap();
+        assert_eq!(
+            tiers,
+            vec![
+                DifficultyTier {
+                    name: String::from("easy"),
+                    rating_range: 800..=1200,
+                },
+                DifficultyTier {
+                    name: String::from("very hard"),
+                    rating_range: 2000..=2400,
+                },
+            ]
+        );
+        assert_eq!(parse_difficulty_tiers(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn parse_difficulty_tiers_rejects_invalid_tiers() {
+        assert!(parse_difficulty_tiers("easy").is_err());
+        assert!(parse_difficulty_tiers("easy 800").is_err());
+        assert!(parse_difficulty_tiers("easy 1200-800").is_err());
+        assert!(parse_difficulty_tiers("easy 800-1200, easy 1200-1600").is_err());
+    }
+
+    #[test]
+    fn parse_theme_reads_tags_and_range() {
+        let (day, rule) = parse_theme("monday dp, 2-sat 1600-2000").unwrap();
+        assert!(matches!(day, ThemeDay::Weekday(Weekday::Mon)));
+        assert_eq!(
+            rule,
+            Some(ThemeRule {
+                tags: BTreeSet::from([String::from("dp"), String::from("2-sat")]),
+                rating_range: Some(1600..=2000),
+            })
+        );
+
+        // a tag containing a dash is no range
+        let (_, rule) = parse_theme("2024-01-01 2-sat").unwrap();
+        assert_eq!(
+            rule,
+            Some(ThemeRule {
+                tags: BTreeSet::from([String::from("2-sat")]),
+                rating_range: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_theme_without_rule_removes_the_theme() {
+        let (day, rule) = parse_theme("2024-02-29").unwrap();
+        assert!(
+            matches!(day, ThemeDay::Date(date) if date == NaiveDate::from_ymd_opt(2024, 2, 29).unwrap())
+        );
+        assert_eq!(rule, None);
+    }
+
+    #[test]
+    fn parse_theme_rejects_invalid_themes() {
+        assert!(parse_theme("someday dp").is_err());
+        assert!(parse_theme("friday dp 2000-1600").is_err());
+        assert!(parse_theme("friday no-such-tag").is_err());
+    }
+}
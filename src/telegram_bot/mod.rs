@@ -8,9 +8,10 @@ use tokio_graceful_shutdown::SubsystemHandle;
 mod channel_state;
 mod controller;
 mod dispatcher;
+mod problem_list;
 
 use crate::scheduler::SchedulerControlCommand;
-pub use channel_state::ChannelState;
+pub use channel_state::{ChannelState, ReviewMode};
 pub use controller::TelegramControlCommand;
 
 pub async fn subsystem_handler(
@@ -27,7 +28,15 @@ pub async fn subsystem_handler(
     let bot = Arc::new(Bot::from_env());
     let storage = dispatcher::create_storage(options.as_ref()).await?;
     let (shutdown_token, mut join_handle) =
-        dispatcher::setup(bot.clone(), telegram_send, sched_send, storage.clone(), cf_client).await;
+        dispatcher::setup(
+            bot.clone(),
+            options.clone(),
+            telegram_send,
+            sched_send,
+            storage.clone(),
+            cf_client,
+        )
+        .await;
 
     log::info!("Started Telegram Bot");
 
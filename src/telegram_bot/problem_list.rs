@@ -0,0 +1,130 @@
+use crate::codeforces::ProblemIdentifier;
+
+/// Finds the problem URLs in a pasted text or a CSV file, in order of appearance
+pub fn parse_text(text: &str) -> Vec<ProblemIdentifier> {
+    text.split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '"' | '\'' | '<' | '>'))
+        .filter_map(ProblemIdentifier::from_url)
+        .collect()
+}
+
+/// Finds the problems in a JSON document, given either by URL or as Codeforces problem objects
+pub fn parse_json(json: &serde_json::Value) -> Vec<ProblemIdentifier> {
+    match json {
+        serde_json::Value::String(text) => parse_text(text),
+        serde_json::Value::Array(values) => values.iter().flat_map(parse_json).collect(),
+        serde_json::Value::Object(object) => {
+            let contest_id = object.get("contestId").and_then(serde_json::Value::as_u64);
+            let index = object.get("index").and_then(serde_json::Value::as_str);
+            match (contest_id, index) {
+                (Some(contest_id), Some(index)) => vec![ProblemIdentifier::new(contest_id, index)],
+                _ => object.values().flat_map(parse_json).collect(),
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Finds the problems in a CSV file, given by `contestId` and `index` columns if the header has them,
+/// and by URL otherwise
+pub fn parse_csv(text: &str) -> Vec<ProblemIdentifier> {
+    let split_row = |row: &str| -> Vec<String> {
+        row.split([',', ';', '\t'])
+            .map(|cell| cell.trim().trim_matches('"').trim().to_lowercase())
+            .collect()
+    };
+    let mut rows = text.lines().filter(|row| !row.trim().is_empty());
+    let Some(header) = rows.next().map(split_row) else {
+        return Vec::new();
+    };
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|cell| names.contains(&cell.as_str()))
+    };
+    let (Some(contest_id_column), Some(index_column)) = (
+        column(&["contestid", "contest_id", "contest id", "contest"]),
+        column(&["index", "problem", "problem index"]),
+    ) else {
+        return parse_text(text);
+    };
+
+    rows.filter_map(|row| {
+        let cells = split_row(row);
+        let contest_id = cells.get(contest_id_column)?.parse().ok()?;
+        let index = cells.get(index_column)?;
+        (!index.is_empty() && index.chars().all(|c| c.is_ascii_alphanumeric()))
+            .then(|| ProblemIdentifier::new(contest_id, index))
+    })
+    .collect()
+}
+
+/// Finds the problems in a file sent as a document, which is read as JSON or CSV if it has that extension
+pub fn parse_document(
+    file_name: Option<&str>,
+    content: &[u8],
+) -> std::result::Result<Vec<ProblemIdentifier>, String> {
+    let content = std::str::from_utf8(content).map_err(|_| String::from("The file is no text"))?;
+    let extension = file_name
+        .and_then(|file_name| file_name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_lowercase());
+    match extension.as_deref() {
+        Some("json") => {
+            let json = serde_json::from_str(content)
+                .map_err(|error| format!("The file is no valid JSON: {}", error))?;
+            Ok(parse_json(&json))
+        }
+        Some("csv") => Ok(parse_csv(content)),
+        _ => Ok(parse_text(content)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[(u64, &str)]) -> Vec<ProblemIdentifier> {
+        ids.iter()
+            .map(|(contest_id, index)| ProblemIdentifier::new(*contest_id, index))
+            .collect()
+    }
+
+    #[test]
+    fn parse_text_finds_urls_in_order() {
+        let text = "first: https://codeforces.com/contest/1700/problem/C,\n\
+            <https://codeforces.com/problemset/problem/4/A> and \"codeforces.com/gym/1/problem/A\"";
+        assert_eq!(parse_text(text), ids(&[(1700, "C"), (4, "A")]));
+    }
+
+    #[test]
+    fn parse_csv_reads_columns() {
+        let csv =
+            "Name;\"contestId\";\"index\"\nWatermelon;4;a\nBroken;x;B\n\nTheatre Square;1;A\n";
+        assert_eq!(parse_csv(csv), ids(&[(4, "A"), (1, "A")]));
+    }
+
+    #[test]
+    fn parse_csv_without_columns_finds_urls() {
+        let csv = "name,url\nWatermelon,https://codeforces.com/problemset/problem/4/A\n";
+        assert_eq!(parse_csv(csv), ids(&[(4, "A")]));
+    }
+
+    #[test]
+    fn parse_json_reads_urls_and_problem_objects() {
+        let json = serde_json::json!({
+            "problems": [
+                {"contestId": 1700, "index": "c", "name": "ignored"},
+                "https://codeforces.com/contest/4/problem/A",
+            ]
+        });
+        assert_eq!(parse_json(&json), ids(&[(1700, "C"), (4, "A")]));
+    }
+
+    #[test]
+    fn parse_document_chooses_format_by_extension() {
+        let csv = b"contestId,index\n4,A\n";
+        assert_eq!(parse_document(Some("sheet.CSV"), csv), Ok(ids(&[(4, "A")])));
+        assert_eq!(parse_document(Some("sheet.txt"), csv), Ok(Vec::new()));
+        assert!(parse_document(Some("sheet.json"), csv).is_err());
+        assert!(parse_document(None, &[0xff, 0xfe]).is_err());
+    }
+}